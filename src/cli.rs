@@ -1,4 +1,6 @@
-use clap::{Parser, ValueEnum};
+use crate::input::{Policy, Truncation};
+use crate::rename::{ConflictStrategy, Mode};
+use clap::{Parser, Subcommand, ValueEnum};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -6,10 +8,15 @@ use std::path::PathBuf;
     about,
     author,
     version,
-    after_help = "OUTPUT pattern accepts placeholders that have the format of '{G:P}' where 'G' \
-    is the captured group and 'P' is the padding of digits with `0`. Please refer to \
-    https://github.com/yaa110/nomino for more information.",
+    after_help = "OUTPUT pattern accepts placeholders that have the format of '{G?DEFAULT:SPEC|FILTER}' \
+    where 'G' is the captured group, optionally followed by an arithmetic operation such as '+1', \
+    'DEFAULT' is used if the group is missing or empty, 'SPEC' is the padding of digits with `0` \
+    or a format spec such as '>8', and 'FILTER' transforms the value, e.g. 'lower'. '{?G:TEXT}' only \
+    renders 'TEXT' if the group is not empty. Please refer to https://github.com/yaa110/nomino \
+    for more information.",
     next_display_order = None,
+    disable_help_subcommand = true,
+    args_conflicts_with_subcommands = true,
 )]
 pub struct Cli {
     /// Runs in test mode without renaming actual files.
@@ -18,14 +25,72 @@ pub struct Cli {
     /// Recursively creates all parent directories of '<OUTPUT>' if they are missing.
     #[arg(short = 'k', long)]
     pub mkdir: bool,
-    /// Overwrites output files, otherwise, a '_' is prepended to filename.
+    /// Overwrites output files, otherwise, renaming is refused if an output already exists, or a '_' is prepended to its filename with '--allow-conflicts'.
     #[arg(short = 'w', long)]
     pub overwrite: bool,
+    /// Sets how to handle outputs which already exist.
+    #[arg(long, value_name = "STRATEGY", conflicts_with = "overwrite")]
+    pub on_conflict: Option<OnConflict>,
+    /// Sets how outputs are created from their inputs.
+    #[arg(long, value_name = "MODE", default_value = "move")]
+    pub mode: TransferMode,
+    /// Edits the outputs in '$EDITOR' before renaming files. Without regex, sort or map options, lists the entries of the given paths or of the working directory.
+    #[arg(short, long)]
+    pub edit: bool,
+    /// Opens a terminal UI to edit the regex and output patterns with a live preview before renaming files.
+    #[arg(long, conflicts_with_all = ["sort", "map", "edit"])]
+    pub tui: bool,
+    /// Rejects outputs which are not valid filenames on the given platforms.
+    #[arg(long, value_name = "POLICY")]
+    pub sanitize: Option<SanitizePolicy>,
+    /// Replaces the invalid characters of outputs with '<REPLACEMENT>' instead of rejecting them, which may be empty.
+    #[arg(long, value_name = "REPLACEMENT", requires = "sanitize")]
+    pub sanitize_with: Option<String>,
+    /// Shortens the filenames of outputs which are longer than 255 bytes, keeping their extension, instead of rejecting them.
+    #[arg(
+        long,
+        value_name = "STYLE",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "plain"
+    )]
+    pub truncate: Option<TruncateStyle>,
+    /// Asks for confirmation before renaming each file, allowing to skip or edit its output.
+    #[arg(short, long)]
+    pub interactive: bool,
+    /// Moves the index entries of renamed files which are tracked by Git, as 'git mv' does.
+    #[arg(long)]
+    pub git: bool,
+    /// Renames files in '--git' mode even if their outputs have uncommitted changes.
+    #[arg(long, requires = "git")]
+    pub force: bool,
+    /// Rewrites references to renamed files in the text files matched by '<GLOB>' after renaming them.
+    #[arg(long, value_name = "GLOB")]
+    pub update_refs: Option<String>,
+    /// Retargets the symlinks under the working directory which point to renamed files.
+    #[arg(long)]
+    pub retarget_symlinks: bool,
+    /// Retargets the symlinks under '<PATH>' as well (can be repeated).
+    #[arg(long, value_name = "PATH", requires = "retarget_symlinks")]
+    pub symlink_root: Vec<PathBuf>,
+    /// Renames files even if an input or output resolves outside of the working directory.
+    #[arg(long)]
+    pub allow_outside: bool,
+    /// Reverts all renames performed in this run if any of them fails, which cannot be combined with
+    /// '--overwrite', 'keep-newer' or 'keep-larger'.
+    #[arg(long)]
+    pub atomic: bool,
+    /// Compares the contents of files moved across filesystems with their copies before removing them.
+    #[arg(long)]
+    pub verify: bool,
+    /// Renames files even if several inputs share an output or an output already exists.
+    #[arg(long)]
+    pub allow_conflicts: bool,
     /// Does not preserve the extension of input files in 'sort' and 'regex' options.
     #[arg(short = 'E', long = "no-extension")]
     pub no_extension: bool,
     /// Sets the working directory.
-    #[arg(short, long = "dir", value_name = "PATH")]
+    #[arg(short, long = "dir", value_name = "PATH", global = true)]
     pub directory: Option<PathBuf>,
     /// Optional value to set the maximum of subdirectory depth value in 'regex' mode.
     #[arg(long, value_name = "DEPTH")]
@@ -37,7 +102,7 @@ pub struct Cli {
     #[arg(short, long, value_name = "PATH")]
     pub generate: Option<PathBuf>,
     /// Does not print the map table to stdout.
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub quiet: bool,
     /// Sets the path of map file to be used for renaming files.
     #[arg(short, long, value_name = "PATH", visible_alias = "from-file")]
@@ -51,6 +116,8 @@ pub struct Cli {
     /// OUTPUT is the pattern to be used for renaming files, and SOURCE is the optional regex pattern to match by filenames. SOURCE has the same function as -r option.
     #[arg(value_name = "[SOURCE] OUTPUT")]
     pub output: Vec<String>,
+    #[command(subcommand)]
+    pub command: Option<Command>,
 }
 
 impl Cli {
@@ -59,6 +126,134 @@ impl Cli {
     }
 }
 
+#[derive(Subcommand)]
+pub enum Command {
+    /// Completes or reverts a batch which has been interrupted, e.g. by a crash.
+    Recover {
+        /// Reverts the renames of the interrupted batch instead of completing it.
+        #[arg(long)]
+        revert: bool,
+    },
+    /// Reverts the last runs in the working directory.
+    Undo {
+        /// Number of runs to revert.
+        #[arg(default_value_t = 1)]
+        count: usize,
+        /// Reverts the runs even if their files have been modified or moved since.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Performs the last undone runs in the working directory again.
+    Redo {
+        /// Number of runs to perform again.
+        #[arg(default_value_t = 1)]
+        count: usize,
+        /// Performs the runs even if their files have been modified or moved since.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Lists the recorded runs in the working directory.
+    History,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OnConflict {
+    /// Does not rename the input.
+    Skip,
+    /// Stops renaming files.
+    Fail,
+    /// Prepends '_' to the filename of the output.
+    Prefix,
+    /// Inserts a counter before the extension of the output, e.g. 'name (2).ext'.
+    SuffixCounter,
+    /// Moves the existing output to a backup, e.g. 'name.ext~'.
+    Backup,
+    /// Keeps the existing output if it is newer than the input, otherwise, overwrites it.
+    KeepNewer,
+    /// Keeps the existing output if it is larger than the input, otherwise, overwrites it.
+    KeepLarger,
+}
+
+impl From<OnConflict> for ConflictStrategy {
+    fn from(value: OnConflict) -> Self {
+        match value {
+            OnConflict::Skip => ConflictStrategy::Skip,
+            OnConflict::Fail => ConflictStrategy::Fail,
+            OnConflict::Prefix => ConflictStrategy::Prefix,
+            OnConflict::SuffixCounter => ConflictStrategy::SuffixCounter,
+            OnConflict::Backup => ConflictStrategy::Backup,
+            OnConflict::KeepNewer => ConflictStrategy::KeepNewer,
+            OnConflict::KeepLarger => ConflictStrategy::KeepLarger,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TransferMode {
+    /// Moves the input to the output.
+    Move,
+    /// Copies the input to the output and keeps the input.
+    Copy,
+    /// Creates the output as a hard link to the input.
+    Hardlink,
+    /// Creates the output as a symlink to the input, relative to the directory of the output.
+    Symlink,
+    /// Creates the output as a symlink to the absolute path of the input.
+    AbsoluteSymlink,
+}
+
+impl From<TransferMode> for Mode {
+    fn from(value: TransferMode) -> Self {
+        match value {
+            TransferMode::Move => Mode::Move,
+            TransferMode::Copy => Mode::Copy,
+            TransferMode::Hardlink => Mode::Hardlink,
+            TransferMode::Symlink => Mode::Symlink,
+            TransferMode::AbsoluteSymlink => Mode::AbsoluteSymlink,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum SanitizePolicy {
+    /// Rejects control characters.
+    Posix,
+    /// Rejects control characters, '<>:"/\|?*', trailing dots and spaces, and reserved names such as 'CON' or 'aux.txt'.
+    Windows,
+    /// Rejects what is invalid on Windows, and leading hyphens and spaces.
+    Portable,
+}
+
+impl From<SanitizePolicy> for Policy {
+    fn from(value: SanitizePolicy) -> Self {
+        match value {
+            SanitizePolicy::Posix => Policy::Posix,
+            SanitizePolicy::Windows => Policy::Windows,
+            SanitizePolicy::Portable => Policy::Portable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum TruncateStyle {
+    /// Cuts the name.
+    Plain,
+    /// Cuts the name and appends '…'.
+    Ellipsis,
+    /// Cuts the name and appends '~' and a hash of the whole name to keep names unique.
+    Hash,
+}
+
+impl From<TruncateStyle> for Truncation {
+    fn from(value: TruncateStyle) -> Self {
+        match value {
+            TruncateStyle::Plain => Truncation::Plain,
+            TruncateStyle::Ellipsis => Truncation::Ellipsis,
+            TruncateStyle::Hash => Truncation::Hash,
+        }
+    }
+}
+
 #[derive(Clone, ValueEnum)]
 pub enum Order {
     /// Sort in ascending order.
@@ -0,0 +1,47 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct Conflict {
+    pub output: String,
+    pub inputs: Vec<String>,
+    pub exists: bool,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ConflictError(Vec<Conflict>);
+
+impl fmt::Display for ConflictError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[conflict] {} output(s) would be overwritten or renamed with a '_' prefix:",
+            self.0.len()
+        )?;
+        for conflict in self.0.iter() {
+            write!(f, "\n  '{}' <- ", conflict.output)?;
+            for (i, input) in conflict.inputs.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "'{input}'")?;
+            }
+            if conflict.exists {
+                write!(f, " (existing file)")?;
+            }
+        }
+        write!(f, "\nrun with '--allow-conflicts' to rename them anyway")
+    }
+}
+
+impl Error for ConflictError {}
+
+impl ConflictError {
+    pub fn new(conflicts: Vec<Conflict>) -> Self {
+        Self(conflicts)
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        self.0.as_slice()
+    }
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+/// Input or output which resolves outside of the working directory.
+#[derive(Debug, PartialEq)]
+pub struct Outside {
+    pub path: String,
+    pub resolved: PathBuf,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ContainmentError(pub Vec<Outside>);
+
+impl fmt::Display for ContainmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[containment] {} path(s) resolve outside of the working directory:",
+            self.0.len()
+        )?;
+        for outside in self.0.iter() {
+            write!(
+                f,
+                "\n  '{}' -> '{}'",
+                outside.path,
+                outside.resolved.display()
+            )?;
+        }
+        write!(f, "\nrun with '--allow-outside' to rename them anyway")
+    }
+}
+
+impl Error for ContainmentError {}
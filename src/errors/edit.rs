@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum EditError {
+    /// The editor could not be run or exited with a failure.
+    Editor(String),
+    /// A line does not start with the number of a file.
+    InvalidLine(usize),
+    /// Files whose line has been deleted.
+    Deleted(Vec<String>),
+    /// Files whose line appears more than once.
+    Duplicated(Vec<String>),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::Editor(message) => write!(f, "[edit] unable to run the editor: {message}"),
+            EditError::InvalidLine(line) => write!(
+                f,
+                "[edit] line {line} must start with the number of a file followed by a tab"
+            ),
+            EditError::Deleted(paths) => {
+                write!(f, "[edit] lines of files have been deleted:")?;
+                for path in paths {
+                    write!(f, "\n  '{path}'")?;
+                }
+                Ok(())
+            }
+            EditError::Duplicated(paths) => {
+                write!(f, "[edit] lines of files have been duplicated:")?;
+                for path in paths {
+                    write!(f, "\n  '{path}'")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for EditError {}
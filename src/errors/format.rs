@@ -4,10 +4,20 @@ use std::fmt;
 #[derive(Debug, PartialEq)]
 pub enum FormatError {
     InvalidEscapeCharacter(usize, char),
-    UnclosedPlaceholder,
-    UnopenedPlaceholder,
+    UnclosedPlaceholder(usize),
+    UnopenedPlaceholder(usize),
     InvalidIndex(String),
-    InvalidPadding(String),
+    InvalidPadding(usize, String),
+    UnknownFilter(usize, String),
+    InvalidFilterArguments(usize, String),
+    InvalidFilterArgument(usize, String),
+    InvalidFilterRegex(usize, String),
+    MissingCondition(usize),
+    InvalidOperand(usize, String),
+    DivisionByZero(usize),
+    /// The value of the placeholder at a position is not an integer.
+    NotANumber(usize, String),
+    ArithmeticOverflow(usize, String),
     EmptyFormatter,
 }
 
@@ -18,20 +28,57 @@ impl fmt::Display for FormatError {
                 f,
                 "[output-format] invalid escape character of '{ch}' at '{pos}'",
             ),
-            FormatError::UnclosedPlaceholder => write!(
+            FormatError::UnclosedPlaceholder(pos) => write!(
                 f,
-                "[output-format] all opened placeholders must be closed by '}}'"
+                "[output-format] the placeholder opened at '{pos}' must be closed by '}}'"
             ),
-            FormatError::UnopenedPlaceholder => write!(
+            FormatError::UnopenedPlaceholder(pos) => write!(
                 f,
-                "[output-format] an unopened placeholder could not be closed by '}}'"
+                "[output-format] an unopened placeholder could not be closed by '}}' at '{pos}'"
             ),
             FormatError::InvalidIndex(index) => {
                 write!(f, "[output-format] unable to parse index of '{index}'")
             }
-            FormatError::InvalidPadding(padding) => {
-                write!(f, "[output-format] unable to parse padding of '{padding}'",)
+            FormatError::InvalidPadding(pos, padding) => write!(
+                f,
+                "[output-format] unable to parse format spec of '{padding}' at '{pos}'",
+            ),
+            FormatError::UnknownFilter(pos, filter) => {
+                write!(f, "[output-format] unknown filter of '{filter}' at '{pos}'")
             }
+            FormatError::InvalidFilterArguments(pos, filter) => write!(
+                f,
+                "[output-format] wrong number of arguments for filter '{filter}' at '{pos}'"
+            ),
+            FormatError::InvalidFilterArgument(pos, argument) => write!(
+                f,
+                "[output-format] invalid filter argument of '{argument}' at '{pos}'"
+            ),
+            FormatError::InvalidFilterRegex(pos, message) => {
+                write!(
+                    f,
+                    "[output-format] invalid filter regex at '{pos}': {message}"
+                )
+            }
+            FormatError::MissingCondition(pos) => write!(
+                f,
+                "[output-format] the conditional segment opened at '{pos}' must refer to a group"
+            ),
+            FormatError::InvalidOperand(pos, operand) => write!(
+                f,
+                "[output-format] unable to parse operand of '{operand}' at '{pos}'"
+            ),
+            FormatError::DivisionByZero(pos) => {
+                write!(f, "[output-format] division by zero at '{pos}'")
+            }
+            FormatError::NotANumber(pos, value) => write!(
+                f,
+                "[output-format] the placeholder at '{pos}' requires an integer but got '{value}'"
+            ),
+            FormatError::ArithmeticOverflow(pos, value) => write!(
+                f,
+                "[output-format] the placeholder at '{pos}' overflows with '{value}'"
+            ),
             FormatError::EmptyFormatter => {
                 write!(f, "[output-format] output formatter must be set")
             }
@@ -39,4 +86,26 @@ impl fmt::Display for FormatError {
     }
 }
 
+impl FormatError {
+    /// Position of the offending character in the format, if known.
+    pub fn position(&self) -> Option<usize> {
+        match self {
+            FormatError::InvalidEscapeCharacter(pos, _)
+            | FormatError::UnclosedPlaceholder(pos)
+            | FormatError::UnopenedPlaceholder(pos)
+            | FormatError::InvalidPadding(pos, _)
+            | FormatError::UnknownFilter(pos, _)
+            | FormatError::InvalidFilterArguments(pos, _)
+            | FormatError::InvalidFilterArgument(pos, _)
+            | FormatError::InvalidFilterRegex(pos, _)
+            | FormatError::MissingCondition(pos)
+            | FormatError::InvalidOperand(pos, _)
+            | FormatError::DivisionByZero(pos)
+            | FormatError::NotANumber(pos, _)
+            | FormatError::ArithmeticOverflow(pos, _) => Some(*pos),
+            FormatError::InvalidIndex(_) | FormatError::EmptyFormatter => None,
+        }
+    }
+}
+
 impl Error for FormatError {}
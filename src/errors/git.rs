@@ -0,0 +1,27 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum GitError {
+    /// Outputs which have uncommitted changes.
+    Dirty(Vec<String>),
+    /// A git command failed with this message.
+    Command(String),
+}
+
+impl fmt::Display for GitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GitError::Dirty(paths) => {
+                write!(f, "[git] outputs have uncommitted changes:")?;
+                for path in paths {
+                    write!(f, "\n  '{path}'")?;
+                }
+                write!(f, "\nrun with '--force' to rename files anyway")
+            }
+            GitError::Command(message) => write!(f, "[git] {message}"),
+        }
+    }
+}
+
+impl Error for GitError {}
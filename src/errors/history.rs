@@ -0,0 +1,42 @@
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub enum HistoryError {
+    NoStateDirectory,
+    NothingToUndo,
+    NothingToRedo,
+    Changed(Vec<String>),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HistoryError::NoStateDirectory => write!(
+                f,
+                "[history] unable to find a state directory, set 'NOMINO_STATE_DIR' to store the history and journals",
+            ),
+            HistoryError::NothingToUndo => {
+                write!(f, "[history] there is no run to undo in this directory")
+            }
+            HistoryError::NothingToRedo => {
+                write!(
+                    f,
+                    "[history] there is no undone run to redo in this directory"
+                )
+            }
+            HistoryError::Changed(paths) => {
+                write!(
+                    f,
+                    "[history] files have been modified or moved since the run:"
+                )?;
+                for path in paths {
+                    write!(f, "\n  '{path}'")?;
+                }
+                write!(f, "\nrun with '--force' to rename them anyway")
+            }
+        }
+    }
+}
+
+impl Error for HistoryError {}
@@ -0,0 +1,32 @@
+use std::error::Error;
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug)]
+pub enum JournalError {
+    Stale(PathBuf),
+    NotFound(PathBuf),
+    Invalid(PathBuf, usize),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::Stale(path) => write!(
+                f,
+                "[journal] '{}' contains an interrupted batch, run 'nomino recover' to complete it or 'nomino recover --revert' to revert it",
+                path.display(),
+            ),
+            JournalError::NotFound(path) => {
+                write!(f, "[journal] no journal found at '{}'", path.display())
+            }
+            JournalError::Invalid(path, line) => write!(
+                f,
+                "[journal] unable to parse line {line} of '{}'",
+                path.display(),
+            ),
+        }
+    }
+}
+
+impl Error for JournalError {}
@@ -0,0 +1,34 @@
+use std::error::Error;
+use std::fmt;
+
+/// Output which has a filename longer than `NAME_MAX` bytes.
+#[derive(Debug, PartialEq)]
+pub struct LongName {
+    pub input: String,
+    pub output: String,
+    /// Length in bytes of its longest filename.
+    pub length: usize,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct LengthError(pub Vec<LongName>);
+
+impl fmt::Display for LengthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[length] {} output(s) have a filename longer than the limit of the filesystem:",
+            self.0.len()
+        )?;
+        for long in self.0.iter() {
+            write!(
+                f,
+                "\n  '{}' <- '{}' ({} bytes)",
+                long.output, long.input, long.length
+            )?;
+        }
+        write!(f, "\nrun with '--truncate' to shorten them")
+    }
+}
+
+impl Error for LengthError {}
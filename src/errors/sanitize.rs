@@ -0,0 +1,43 @@
+use std::error::Error;
+use std::fmt;
+
+/// Output which is not a valid filename according to the sanitization policy.
+#[derive(Debug, PartialEq)]
+pub struct InvalidOutput {
+    pub input: String,
+    pub output: String,
+    pub reason: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum SanitizeError {
+    /// The replacement is itself invalid, for this reason.
+    InvalidReplacement(String, String),
+    InvalidOutputs(Vec<InvalidOutput>),
+}
+
+impl fmt::Display for SanitizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanitizeError::InvalidReplacement(replacement, reason) => {
+                write!(f, "[sanitize] replacement '{replacement}' {reason}")
+            }
+            SanitizeError::InvalidOutputs(invalid) => {
+                write!(f, "[sanitize] {} output(s) are invalid:", invalid.len())?;
+                for invalid in invalid {
+                    write!(
+                        f,
+                        "\n  '{}' <- '{}': {}",
+                        invalid.output, invalid.input, invalid.reason
+                    )?;
+                }
+                write!(
+                    f,
+                    "\nrun with '--sanitize-with' to replace the invalid characters"
+                )
+            }
+        }
+    }
+}
+
+impl Error for SanitizeError {}
@@ -0,0 +1,162 @@
+use crate::errors::{EditError, SourceError};
+use anyhow::Result;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Lists the files to edit, i.e. the entries of the working directory if
+/// `paths` is empty, the entries of each directory in `paths` and the other
+/// paths themselves, in natural order.
+pub fn list_paths(paths: &[String]) -> Result<Vec<String>> {
+    if paths.is_empty() {
+        return list_dir(Path::new("."));
+    }
+    let mut listed = Vec::new();
+    for path in paths {
+        let metadata = fs::symlink_metadata(path)
+            .map_err(|e| SourceError::new(format!("unable to read '{path}': {e}")))?;
+        if metadata.is_dir() {
+            listed.extend(list_dir(Path::new(path))?);
+        } else {
+            listed.push(path.clone());
+        }
+    }
+    Ok(listed)
+}
+
+fn list_dir(dir: &Path) -> Result<Vec<String>> {
+    let mut entries: Vec<String> = fs::read_dir(dir)
+        .map_err(|e| SourceError::new(format!("unable to read '{}': {e}", dir.display())))?
+        .flatten()
+        .map(|entry| {
+            let path = if dir == Path::new(".") {
+                PathBuf::from(entry.file_name())
+            } else {
+                dir.join(entry.file_name())
+            };
+            path.to_string_lossy().to_string()
+        })
+        .collect();
+    entries.sort_by(|a, b| natord::compare(a, b));
+    Ok(entries)
+}
+
+/// Writes the outputs of `pairs` to a temporary file, one numbered line per
+/// file, opens it in `$VISUAL` or `$EDITOR` and returns the pairs whose output
+/// has been changed. The file is created with a random name and is only
+/// readable by the user, and it is removed afterwards.
+pub fn edit(pairs: Vec<(String, String)>) -> Result<Vec<(String, String)>> {
+    let mut file = tempfile::Builder::new()
+        .prefix("nomino-edit-")
+        .suffix(".txt")
+        .tempfile()?;
+    file.write_all(write_lines(pairs.as_slice()).as_bytes())?;
+    file.flush()?;
+    run_editor(file.path())?;
+    let contents = fs::read_to_string(file.path())?;
+    Ok(parse_lines(pairs.as_slice(), contents.as_str())?)
+}
+
+fn write_lines(pairs: &[(String, String)]) -> String {
+    let width = pairs.len().to_string().len();
+    pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (_, output))| format!("{:0width$}\t{output}\n", i + 1))
+        .collect()
+}
+
+fn run_editor(path: &Path) -> Result<()> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| String::from("vi"));
+    let mut words = editor.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| EditError::Editor(String::from("the editor is empty")))?;
+    let status = Command::new(program)
+        .args(words)
+        .arg(path)
+        .status()
+        .map_err(|e| EditError::Editor(format!("'{program}': {e}")))?;
+    if !status.success() {
+        return Err(EditError::Editor(format!("'{program}' exited with {status}")).into());
+    }
+    Ok(())
+}
+
+/// Matches the edited lines with `pairs` by their number. Every line must be
+/// kept exactly once, and empty lines are ignored.
+fn parse_lines(
+    pairs: &[(String, String)],
+    contents: &str,
+) -> Result<Vec<(String, String)>, EditError> {
+    let mut edited: HashMap<usize, Vec<&str>> = HashMap::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let index = line
+            .split_once('\t')
+            .filter(|(_, output)| !output.is_empty())
+            .and_then(|(number, output)| Some((number.trim().parse::<usize>().ok()?, output)))
+            .filter(|(number, _)| (1..=pairs.len()).contains(number));
+        let Some((number, output)) = index else {
+            return Err(EditError::InvalidLine(i + 1));
+        };
+        edited.entry(number - 1).or_default().push(output);
+    }
+    let select = |keep: fn(usize) -> bool| -> Vec<String> {
+        pairs
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| keep(edited.get(i).map_or(0, Vec::len)))
+            .map(|(_, (input, _))| input.clone())
+            .collect()
+    };
+    let deleted = select(|count| count == 0);
+    if !deleted.is_empty() {
+        return Err(EditError::Deleted(deleted));
+    }
+    let duplicated = select(|count| count > 1);
+    if !duplicated.is_empty() {
+        return Err(EditError::Duplicated(duplicated));
+    }
+    Ok(pairs
+        .iter()
+        .enumerate()
+        .map(|(i, (input, _))| (input.clone(), edited[&i][0].to_string()))
+        .filter(|(input, output)| input != output)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pairs;
+
+    #[test]
+    fn test_parse_lines() {
+        let original = pairs(&[("a", "a"), ("b", "x"), ("c", "c")]);
+        assert_eq!(write_lines(original.as_slice()), "1\ta\n2\tx\n3\tc\n");
+        assert_eq!(
+            parse_lines(original.as_slice(), "3\tdir/d\n\n1\ta\n2\ty\n"),
+            Ok(pairs(&[("b", "y"), ("c", "dir/d")]))
+        );
+        assert_eq!(
+            parse_lines(original.as_slice(), "1\ta\n3\tc\n"),
+            Err(EditError::Deleted(vec!["b".to_string()]))
+        );
+        assert_eq!(
+            parse_lines(original.as_slice(), "1\ta\n2\tx\n2\ty\n3\tc\n"),
+            Err(EditError::Duplicated(vec!["b".to_string()]))
+        );
+        assert_eq!(
+            parse_lines(original.as_slice(), "1\ta\n4\tx\n"),
+            Err(EditError::InvalidLine(2))
+        );
+    }
+}
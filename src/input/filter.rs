@@ -0,0 +1,284 @@
+use crate::errors::FormatError;
+use regex::Regex;
+use std::ops::Range;
+
+const FILTERS: [&str; 13] = [
+    "lower",
+    "upper",
+    "title",
+    "snake",
+    "kebab",
+    "camel",
+    "pascal",
+    "replace",
+    "replace_re",
+    "slice",
+    "trim",
+    "trim_chars",
+    "collapse_ws",
+];
+
+/// Regex argument of a filter, compared by its pattern.
+#[derive(Debug)]
+pub struct Pattern(Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Transformation applied to the value of a placeholder, e.g. `{1|lower}`.
+#[derive(Debug, PartialEq)]
+pub enum Filter {
+    Lower,
+    Upper,
+    /// Capitalizes each word, keeping the characters between words.
+    Title,
+    Snake,
+    Kebab,
+    Camel,
+    Pascal,
+    /// Replaces all occurrences of a string.
+    Replace(String, String),
+    /// Replaces all matches of a regex, where `$1` refers to its groups.
+    ReplaceRe(Pattern, String),
+    /// Keeps the characters from a start to an end index, where negative
+    /// indices count from the end.
+    Slice(Option<isize>, Option<isize>),
+    Trim,
+    TrimChars(Vec<char>),
+    /// Replaces each run of whitespace with a single space.
+    CollapseWs,
+}
+
+impl Filter {
+    /// Parses the filter `name` at `position` with its `arguments`, along with
+    /// their position.
+    pub fn parse(
+        position: usize,
+        name: &str,
+        arguments: Vec<(usize, String)>,
+    ) -> Result<Self, FormatError> {
+        let index = |(position, index): &(usize, String)| -> Result<Option<isize>, FormatError> {
+            if index.is_empty() {
+                return Ok(None);
+            }
+            index
+                .parse()
+                .map(Some)
+                .map_err(|_| FormatError::InvalidFilterArgument(*position, index.clone()))
+        };
+        Ok(match (name, arguments.as_slice()) {
+            ("lower", []) => Filter::Lower,
+            ("upper", []) => Filter::Upper,
+            ("title", []) => Filter::Title,
+            ("snake", []) => Filter::Snake,
+            ("kebab", []) => Filter::Kebab,
+            ("camel", []) => Filter::Camel,
+            ("pascal", []) => Filter::Pascal,
+            ("replace", [(_, from), (_, to)]) => Filter::Replace(from.clone(), to.clone()),
+            ("replace_re", [(position, pattern), (_, to)]) => Filter::ReplaceRe(
+                Pattern(
+                    Regex::new(pattern)
+                        .map_err(|e| FormatError::InvalidFilterRegex(*position, e.to_string()))?,
+                ),
+                to.clone(),
+            ),
+            ("slice", [start]) => Filter::Slice(index(start)?, None),
+            ("slice", [start, end]) => Filter::Slice(index(start)?, index(end)?),
+            ("trim", []) => Filter::Trim,
+            ("trim_chars", [(_, chars)]) => Filter::TrimChars(chars.chars().collect()),
+            ("collapse_ws", []) => Filter::CollapseWs,
+            (name, _) if FILTERS.contains(&name) => {
+                return Err(FormatError::InvalidFilterArguments(
+                    position,
+                    name.to_string(),
+                ));
+            }
+            (name, _) => return Err(FormatError::UnknownFilter(position, name.to_string())),
+        })
+    }
+
+    pub fn apply(&self, value: &str) -> String {
+        match self {
+            Filter::Lower => value.to_lowercase(),
+            Filter::Upper => value.to_uppercase(),
+            Filter::Title => {
+                let mut titled = String::with_capacity(value.len());
+                let mut end = 0;
+                for word in words(value) {
+                    titled.push_str(&value[end..word.start]);
+                    titled.push_str(capitalize(&value[word.clone()]).as_str());
+                    end = word.end;
+                }
+                titled.push_str(&value[end..]);
+                titled
+            }
+            Filter::Snake => join_words(value, "_", |_, word| word.to_lowercase()),
+            Filter::Kebab => join_words(value, "-", |_, word| word.to_lowercase()),
+            Filter::Camel => join_words(value, "", |i, word| {
+                if i == 0 {
+                    word.to_lowercase()
+                } else {
+                    capitalize(word)
+                }
+            }),
+            Filter::Pascal => join_words(value, "", |_, word| capitalize(word)),
+            Filter::Replace(from, to) if !from.is_empty() => value.replace(from, to),
+            Filter::Replace(_, _) => value.to_string(),
+            Filter::ReplaceRe(Pattern(re), to) => re.replace_all(value, to.as_str()).to_string(),
+            Filter::Slice(start, end) => {
+                let chars: Vec<char> = value.chars().collect();
+                let resolve = |index: isize| {
+                    if index < 0 {
+                        chars.len().saturating_sub(index.unsigned_abs())
+                    } else {
+                        index.unsigned_abs().min(chars.len())
+                    }
+                };
+                let start = start.map_or(0, resolve);
+                let end = end.map_or(chars.len(), resolve);
+                chars[start..end.max(start)].iter().collect()
+            }
+            Filter::Trim => value.trim().to_string(),
+            Filter::TrimChars(chars) => value.trim_matches(chars.as_slice()).to_string(),
+            Filter::CollapseWs => value.split_whitespace().collect::<Vec<_>>().join(" "),
+        }
+    }
+}
+
+fn join_words(value: &str, separator: &str, case: impl Fn(usize, &str) -> String) -> String {
+    words(value)
+        .into_iter()
+        .enumerate()
+        .map(|(i, word)| case(i, &value[word]))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// Uppercases the first character of `word` and lowercases the others.
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    let Some(first) = chars.next() else {
+        return String::new();
+    };
+    let mut capitalized: String = first.to_uppercase().collect();
+    capitalized.push_str(chars.as_str().to_lowercase().as_str());
+    capitalized
+}
+
+/// Byte ranges of the words of `value`, which are runs of alphanumeric
+/// characters also split on camelCase boundaries, e.g. `fooBar` or
+/// `HTTPServer`.
+fn words(value: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let chars: Vec<(usize, char)> = value.char_indices().collect();
+    for (i, &(index, ch)) in chars.iter().enumerate() {
+        if !ch.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                words.push(start..index);
+            }
+            continue;
+        }
+        let Some(word_start) = start else {
+            start = Some(index);
+            continue;
+        };
+        let previous = chars[i - 1].1;
+        let next = chars.get(i + 1).map(|(_, ch)| *ch);
+        let is_boundary = ch.is_uppercase()
+            && (previous.is_lowercase()
+                || (previous.is_uppercase() && next.is_some_and(char::is_lowercase)));
+        if is_boundary {
+            words.push(word_start..index);
+            start = Some(index);
+        }
+    }
+    if let Some(start) = start {
+        words.push(start..value.len());
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_words() {
+        let value = "the HTTPServer.fooBar_baz 2";
+        let words: Vec<&str> = words(value).into_iter().map(|word| &value[word]).collect();
+        assert_eq!(words, ["the", "HTTP", "Server", "foo", "Bar", "baz", "2"]);
+    }
+
+    #[test]
+    fn test_apply() {
+        let value = "the quick.brownFox_jumps";
+        assert_eq!(Filter::Lower.apply("ÉTÉ Straße"), "été straße");
+        assert_eq!(Filter::Upper.apply("été straße"), "ÉTÉ STRASSE");
+        assert_eq!(Filter::Title.apply(value), "The Quick.BrownFox_Jumps");
+        assert_eq!(Filter::Snake.apply(value), "the_quick_brown_fox_jumps");
+        assert_eq!(Filter::Kebab.apply(value), "the-quick-brown-fox-jumps");
+        assert_eq!(Filter::Camel.apply(value), "theQuickBrownFoxJumps");
+        assert_eq!(Filter::Pascal.apply(value), "TheQuickBrownFoxJumps");
+        assert_eq!(Filter::Pascal.apply("élan vital"), "ÉlanVital");
+    }
+
+    #[test]
+    fn test_apply_arguments() {
+        let parse = |name: &str, arguments: &[&str]| {
+            Filter::parse(
+                0,
+                name,
+                arguments
+                    .iter()
+                    .map(|argument| (0, argument.to_string()))
+                    .collect(),
+            )
+            .unwrap()
+        };
+        let value = "  the.big  bang  ";
+        assert_eq!(
+            parse("replace", &[".", " "]).apply(value),
+            "  the big  bang  "
+        );
+        assert_eq!(
+            parse("replace_re", &[r"\s+", "_"]).apply(value),
+            "_the.big_bang_"
+        );
+        assert_eq!(
+            parse("replace_re", &[r"(\w+)\.(\w+)", "$2.$1"]).apply("a.b"),
+            "b.a"
+        );
+        assert_eq!(parse("trim", &[]).apply(value), "the.big  bang");
+        assert_eq!(parse("trim_chars", &["-_"]).apply("-_a-b_-"), "a-b");
+        assert_eq!(parse("collapse_ws", &[]).apply(value), "the.big bang");
+        assert_eq!(parse("slice", &["0", "3"]).apply("élan vital"), "éla");
+        assert_eq!(parse("slice", &["-5"]).apply("élan vital"), "vital");
+        assert_eq!(parse("slice", &["", "-6"]).apply("élan vital"), "élan");
+        assert_eq!(parse("slice", &["4", "2"]).apply("élan vital"), "");
+        assert_eq!(parse("slice", &["2", "99"]).apply("élan"), "an");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Filter::parse(3, "slice", vec![(9, String::from("a"))]),
+            Err(FormatError::InvalidFilterArgument(9, String::from("a")))
+        );
+        assert_eq!(
+            Filter::parse(3, "trim", vec![(8, String::from("a"))]),
+            Err(FormatError::InvalidFilterArguments(3, String::from("trim")))
+        );
+        assert!(matches!(
+            Filter::parse(
+                3,
+                "replace_re",
+                vec![(14, String::from("(")), (16, String::new())]
+            ),
+            Err(FormatError::InvalidFilterRegex(14, _))
+        ));
+    }
+}
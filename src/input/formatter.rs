@@ -1,11 +1,55 @@
-use super::{Provider, provider::Capture};
+use super::{Filter, Provider, Spec, provider::Capture};
 use crate::errors::FormatError;
+use std::path;
+
+#[derive(Debug, PartialEq)]
+enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Integer operation applied to the value of a placeholder, e.g. `{1+1}`.
+#[derive(Debug, PartialEq)]
+struct Arithmetic {
+    /// Position of the placeholder in the format.
+    position: usize,
+    operator: Operator,
+    operand: i64,
+}
+
+impl Arithmetic {
+    fn apply(&self, value: &str) -> Result<String, FormatError> {
+        let number: i64 = value
+            .parse()
+            .map_err(|_| FormatError::NotANumber(self.position, value.to_string()))?;
+        match self.operator {
+            Operator::Add => number.checked_add(self.operand),
+            Operator::Sub => number.checked_sub(self.operand),
+            Operator::Mul => number.checked_mul(self.operand),
+            Operator::Div => number.checked_div(self.operand),
+        }
+        .map(|number| number.to_string())
+        .ok_or_else(|| FormatError::ArithmeticOverflow(self.position, value.to_string()))
+    }
+}
 
 #[derive(Debug, PartialEq)]
 enum Segment {
     PlaceHolder {
-        padding: Option<usize>,
+        spec: Option<Spec>,
         capture: Capture,
+        /// Operation applied to the value of the capture, but not to the default.
+        arithmetic: Option<Arithmetic>,
+        /// Value used if the capture is missing or empty.
+        default: Option<String>,
+        filters: Vec<Filter>,
+    },
+    /// Segments only rendered if the capture is present and not empty.
+    Conditional {
+        capture: Capture,
+        segments: Vec<Segment>,
     },
     String(String),
 }
@@ -15,110 +59,276 @@ pub struct Formatter(Vec<Segment>);
 
 impl Formatter {
     pub fn new(format: &str) -> Result<Self, FormatError> {
-        let mut segments = Vec::new();
-        let mut should_escape = false;
-        let mut is_parsing_capture = false;
-        let mut is_parsing_padding = false;
-        let mut current_segment = String::new();
-        let mut current_capture = Capture::Index(0);
-        let mut current_padding: Option<usize> = None;
-        let mut incremental_index: usize = 1;
-        for (i, ch) in format.chars().enumerate() {
-            if !should_escape && ch == '\\' {
-                should_escape = true;
-                continue;
+        let mut parser = Parser {
+            chars: format.chars().collect(),
+            position: 0,
+            incremental_index: 1,
+        };
+        Ok(Self(parser.segments(None)?))
+    }
+
+    /// Number of path separators which the format itself may add to outputs,
+    /// i.e. in its text, defaults and filter replacements.
+    pub fn separators(&self) -> usize {
+        count_separators(self.0.as_slice())
+    }
+
+    /// Formats the captures of `provider`, or fails if an arithmetic
+    /// placeholder refers to a value which is not an integer.
+    pub fn format(&self, provider: impl Provider) -> Result<String, FormatError> {
+        let mut formatted = String::new();
+        format_segments(self.0.as_slice(), &provider, &mut formatted)?;
+        Ok(formatted)
+    }
+}
+
+fn format_segments(
+    segments: &[Segment],
+    provider: &impl Provider,
+    formatted: &mut String,
+) -> Result<(), FormatError> {
+    for segment in segments {
+        match segment {
+            Segment::PlaceHolder {
+                spec,
+                capture,
+                arithmetic,
+                default,
+                filters,
+            } => {
+                let var = match (provider.provide(capture), default) {
+                    (Some(var), _) if !var.is_empty() => match arithmetic {
+                        Some(arithmetic) => arithmetic.apply(var)?,
+                        None => var.to_string(),
+                    },
+                    (_, Some(default)) => default.clone(),
+                    (Some(var), None) => var.to_string(),
+                    (None, None) => continue,
+                };
+                let var = filters
+                    .iter()
+                    .fold(var, |var, filter| filter.apply(var.as_str()));
+                match spec {
+                    Some(spec) => formatted.push_str(spec.apply(var).as_str()),
+                    None => formatted.push_str(var.as_str()),
+                }
+            }
+            Segment::Conditional { capture, segments } => {
+                if provider.provide(capture).is_some_and(|var| !var.is_empty()) {
+                    format_segments(segments, provider, formatted)?;
+                }
             }
-            if should_escape && ch != '{' && ch != '}' && ch != '\\' {
-                return Err(FormatError::InvalidEscapeCharacter(i, ch));
+            Segment::String(string) => formatted.push_str(string),
+        }
+    }
+    Ok(())
+}
+
+fn count_separators(segments: &[Segment]) -> usize {
+    let count = |string: &str| string.matches(path::is_separator).count();
+    segments
+        .iter()
+        .map(|segment| match segment {
+            Segment::PlaceHolder {
+                default, filters, ..
+            } => {
+                default.as_deref().map_or(0, count)
+                    + filters
+                        .iter()
+                        .map(|filter| match filter {
+                            Filter::Replace(_, to) | Filter::ReplaceRe(_, to) => count(to),
+                            _ => 0,
+                        })
+                        .sum::<usize>()
             }
-            match ch {
-                '{' if !should_escape && !is_parsing_capture && !is_parsing_padding => {
+            Segment::Conditional { segments, .. } => count_separators(segments),
+            Segment::String(string) => count(string),
+        })
+        .sum()
+}
+
+/// Parses a format, where positions are counted in characters.
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    incremental_index: usize,
+}
+
+impl Parser {
+    /// Parses segments until the end of the format, or until the `}` closing
+    /// the conditional segment `opened` at a position.
+    fn segments(&mut self, opened: Option<usize>) -> Result<Vec<Segment>, FormatError> {
+        let mut segments = Vec::new();
+        let mut current_segment = String::new();
+        loop {
+            match self.next() {
+                Some('\\') => current_segment.extend(self.escaped()?),
+                Some('{') => {
                     if !current_segment.is_empty() {
-                        segments.push(Segment::String(current_segment));
-                        current_segment = String::new();
+                        segments.push(Segment::String(std::mem::take(&mut current_segment)));
                     }
-                    is_parsing_capture = true;
-                }
-                '}' if !should_escape => {
-                    if !is_parsing_capture && !is_parsing_padding {
-                        return Err(FormatError::UnopenedPlaceholder);
-                    }
-                    if current_segment.is_empty() {
-                        if is_parsing_capture {
-                            current_capture = Capture::Index(incremental_index);
-                            incremental_index += 1;
-                        } else if is_parsing_padding {
-                            current_padding = None;
-                        }
-                    } else if is_parsing_capture {
-                        current_capture = current_segment.as_str().into();
-                        current_padding = None;
-                    } else if is_parsing_padding {
-                        current_padding =
-                            Some(current_segment.as_str().parse().map_err(|_| {
-                                FormatError::InvalidPadding(current_segment.clone())
-                            })?);
-                    }
-                    segments.push(Segment::PlaceHolder {
-                        padding: current_padding,
-                        capture: current_capture,
-                    });
-                    current_segment.clear();
-                    current_padding = None;
-                    current_capture = Capture::Index(0);
-                    is_parsing_capture = false;
-                    is_parsing_padding = false;
-                }
-                ':' if is_parsing_capture => {
-                    is_parsing_capture = false;
-                    is_parsing_padding = true;
-                    if current_segment.is_empty() {
-                        current_capture = Capture::Index(incremental_index);
-                        incremental_index += 1;
+                    let start = self.position - 1;
+                    if self.chars.get(self.position) == Some(&'?') {
+                        self.position += 1;
+                        segments.push(self.conditional(start)?);
                     } else {
-                        current_capture = current_segment.as_str().into();
-                        current_segment.clear();
+                        segments.push(self.placeholder(start)?);
                     }
                 }
-                _ => {
-                    current_segment.push(ch);
-                    should_escape = false;
-                }
+                Some('}') if opened.is_some() => break,
+                Some('}') => return Err(FormatError::UnopenedPlaceholder(self.position - 1)),
+                Some(ch) => current_segment.push(ch),
+                None => match opened {
+                    Some(start) => return Err(FormatError::UnclosedPlaceholder(start)),
+                    None => break,
+                },
             }
         }
-        if is_parsing_capture || is_parsing_padding {
-            return Err(FormatError::UnclosedPlaceholder);
-        }
         if !current_segment.is_empty() {
             segments.push(Segment::String(current_segment));
         }
-        Ok(Self(segments))
+        Ok(segments)
     }
 
-    pub fn format(&self, provider: impl Provider) -> String {
-        let mut formatted = String::new();
-        for segment in self.0.as_slice() {
-            match segment {
-                Segment::PlaceHolder { padding, capture } => {
-                    let Some(var) = provider.provide(capture) else {
-                        continue;
-                    };
-                    if let Some((padding, digits)) =
-                        padding.zip(var.parse().map(|n: usize| n.to_string()).ok())
-                    {
-                        if digits.len() < padding {
-                            let diff = padding - digits.len();
-                            (0..diff).for_each(|_| formatted.push('0'));
-                        }
-                        formatted.push_str(digits.as_str());
-                        continue;
+    /// Parses a conditional segment of the form `{?G:...}` opened at `start`,
+    /// whose body may contain placeholders.
+    fn conditional(&mut self, start: usize) -> Result<Segment, FormatError> {
+        let (capture, stop) = self.read_until(start, &[':', '}'], false)?;
+        if capture.is_empty() {
+            return Err(FormatError::MissingCondition(start));
+        }
+        let segments = if stop == ':' {
+            self.segments(Some(start))?
+        } else {
+            Vec::new()
+        };
+        Ok(Segment::Conditional {
+            capture: capture.as_str().into(),
+            segments,
+        })
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let ch = self.chars.get(self.position).copied();
+        if ch.is_some() {
+            self.position += 1;
+        }
+        ch
+    }
+
+    /// Reads the character escaped by a backslash, or `None` if the format ends
+    /// with the backslash.
+    fn escaped(&mut self) -> Result<Option<char>, FormatError> {
+        match self.next() {
+            Some(ch) if ch != '{' && ch != '}' && ch != '\\' => {
+                Err(FormatError::InvalidEscapeCharacter(self.position - 1, ch))
+            }
+            ch => Ok(ch),
+        }
+    }
+
+    /// Reads the text of the placeholder opened at `start` until one of
+    /// `stops`, and returns it along with the stop. In filter arguments, `:` and
+    /// `|` may be escaped as well, and other escapes are kept as they are, e.g.
+    /// `\s` in a regex.
+    fn read_until(
+        &mut self,
+        start: usize,
+        stops: &[char],
+        is_argument: bool,
+    ) -> Result<(String, char), FormatError> {
+        let mut text = String::new();
+        loop {
+            match self.next() {
+                Some('\\') if is_argument => match self.next() {
+                    Some(ch) if matches!(ch, '{' | '}' | '\\' | ':' | '|') => text.push(ch),
+                    Some(ch) => {
+                        text.push('\\');
+                        text.push(ch);
                     }
-                    formatted.push_str(var);
-                }
-                Segment::String(string) => formatted.push_str(string),
+                    None => return Err(FormatError::UnclosedPlaceholder(start)),
+                },
+                Some('\\') => match self.escaped()? {
+                    Some(ch) => text.push(ch),
+                    None => return Err(FormatError::UnclosedPlaceholder(start)),
+                },
+                Some(ch) if stops.contains(&ch) => return Ok((text, ch)),
+                Some(ch) => text.push(ch),
+                None => return Err(FormatError::UnclosedPlaceholder(start)),
+            }
+        }
+    }
+
+    /// Parses a placeholder of the form `{G+N?D:S|F:A:...|...}` opened at
+    /// `start`.
+    fn placeholder(&mut self, start: usize) -> Result<Segment, FormatError> {
+        let (capture, mut stop) =
+            self.read_until(start, &['+', '-', '*', '/', '?', ':', '|', '}'], false)?;
+        let capture = if capture.is_empty() {
+            self.incremental_index += 1;
+            Capture::Index(self.incremental_index - 1)
+        } else {
+            capture.as_str().into()
+        };
+        let mut arithmetic = None;
+        if let Some(operator) = match stop {
+            '+' => Some(Operator::Add),
+            '-' => Some(Operator::Sub),
+            '*' => Some(Operator::Mul),
+            '/' => Some(Operator::Div),
+            _ => None,
+        } {
+            let operand_start = self.position;
+            let (text, next) = self.read_until(start, &['?', ':', '|', '}'], false)?;
+            let operand = text
+                .parse()
+                .map_err(|_| FormatError::InvalidOperand(operand_start, text.clone()))?;
+            if operator == Operator::Div && operand == 0 {
+                return Err(FormatError::DivisionByZero(operand_start));
+            }
+            arithmetic = Some(Arithmetic {
+                position: start,
+                operator,
+                operand,
+            });
+            stop = next;
+        }
+        let mut default = None;
+        if stop == '?' {
+            let (text, next) = self.read_until(start, &[':', '|', '}'], true)?;
+            default = Some(text);
+            stop = next;
+        }
+        let mut spec = None;
+        if stop == ':' {
+            let spec_start = self.position;
+            let (text, next) = self.read_until(start, &['|', '}'], true)?;
+            if !text.is_empty() {
+                spec = Some(Spec::parse(spec_start, text.as_str())?);
             }
+            stop = next;
         }
-        formatted
+        let mut filters = Vec::new();
+        while stop == '|' {
+            let filter_start = self.position;
+            let (name, mut next) = self.read_until(start, &[':', '|', '}'], false)?;
+            let mut arguments = Vec::new();
+            while next == ':' {
+                let argument_start = self.position;
+                let (argument, after) = self.read_until(start, &[':', '|', '}'], true)?;
+                arguments.push((argument_start, argument));
+                next = after;
+            }
+            filters.push(Filter::parse(filter_start, name.as_str(), arguments)?);
+            stop = next;
+        }
+        Ok(Segment::PlaceHolder {
+            spec,
+            capture,
+            arithmetic,
+            default,
+            filters,
+        })
     }
 }
 
@@ -127,6 +337,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::expect_fun_call)]
     fn test_valid_formats() {
         let mut format_vars_expected = vec![
             ("{}", vec!["first", "second"], "second"),
@@ -177,12 +388,63 @@ mod tests {
                 vec!["0", "1", "2"],
                 "init 00001{}02 end",
             ),
+            ("{|upper}", vec!["first", "second"], "SECOND"),
+            (
+                "{0|snake|upper}",
+                vec!["Nomino S1.E1", "second"],
+                "NOMINO_S1_E1",
+            ),
+            ("{1:3|lower}", vec!["0", "5"], "005"),
+            ("{:2|kebab}-{|pascal}", vec!["0", "a b", "c d"], "a-b-CD"),
+            (
+                "{1|replace:.: |slice:0:7}",
+                vec!["0", "the.big.bang"],
+                "the big",
+            ),
+            (r"{1|replace_re:\s+:_}", vec!["0", "a  b c"], "a_b_c"),
+            (r"{1|replace:\:\|:\}}", vec!["0", "a:|b"], "a}b"),
+            (r"{1|replace_re:\d\{2\}:#}", vec!["0", "a123"], "a#3"),
+            (
+                "{1|trim_chars:-_|collapse_ws}",
+                vec!["0", "-_a   b_-"],
+                "a b",
+            ),
+            ("{1?unknown}", vec!["0", ""], "unknown"),
+            ("{2?unknown}", vec!["0", "1"], "unknown"),
+            ("{1?unknown}", vec!["0", "1"], "1"),
+            ("{1?}", vec!["0"], ""),
+            ("{1?7:3}", vec!["0"], "007"),
+            ("{1?no name|upper}", vec!["0"], "NO NAME"),
+            (r"{1?a\:b\}}", vec!["0"], "a:b}"),
+            ("{1}{?2: - {2}}", vec!["0", "a", "b"], "a - b"),
+            ("{1}{?2: - {2}}", vec!["0", "a", ""], "a"),
+            ("{1}{?2: - {2}}", vec!["0", "a"], "a"),
+            ("{?1:{?2:{1}-{2}}}", vec!["0", "a", "b"], "a-b"),
+            (r"{?1:\{{1}\}}", vec!["0", "a"], "{a}"),
+            ("{?1}", vec!["0", "a"], ""),
+            ("{1+1}", vec!["0", "0"], "1"),
+            ("{1-10:3}", vec!["0", "15"], "005"),
+            ("{1-10:3}", vec!["0", "5"], "-5"),
+            ("{1*2}", vec!["0", "007"], "14"),
+            ("{1/2}", vec!["0", "7"], "3"),
+            ("{1+-1}", vec!["0", "1"], "0"),
+            ("{+100}", vec!["0", "2"], "102"),
+            ("{1+1?none}", vec!["0", ""], "none"),
+            ("{2+1?0:2}", vec!["0", "1"], "00"),
+            ("{1+1:2|replace:0:o}", vec!["0", "9"], "1o"),
+            ("{1:*^7}", vec!["0", "abc"], "**abc**"),
+            ("{1:.2}", vec!["0", "3.14159"], "3.14"),
+            ("{1:+03}", vec!["0", "7"], "+07"),
+            ("{1-10:03}", vec!["0", "5"], "-05"),
+            ("{1:04x|upper}", vec!["0", "255"], "00ff"),
+            (r"{1:\|>4}", vec!["0", "ab"], "||ab"),
+            ("{1?none:>6}", vec!["0"], "  none"),
         ];
 
         while let Some((format, vars, expected)) = format_vars_expected.pop() {
             let output = Formatter::new(format)
                 .expect(format!("unable to parse format '{}'", format).as_str());
-            let actual = output.format(vars);
+            let actual = output.format(vars).unwrap();
             assert_eq!(actual, expected);
         }
     }
@@ -190,18 +452,71 @@ mod tests {
     #[test]
     fn test_invalid_formats() {
         let mut format_error = vec![
-            ("}", FormatError::UnopenedPlaceholder),
+            ("}", FormatError::UnopenedPlaceholder(0)),
             (r"\a", FormatError::InvalidEscapeCharacter(1, 'a')),
-            ("2:5}", FormatError::UnopenedPlaceholder),
-            (r"\{2:5}", FormatError::UnopenedPlaceholder),
-            (r"{2:5\}", FormatError::UnclosedPlaceholder),
-            ("{2:5a}", FormatError::InvalidPadding("5a".to_string())),
-            ("init {2:5", FormatError::UnclosedPlaceholder),
-            ("init {2:5 end", FormatError::UnclosedPlaceholder),
+            ("2:5}", FormatError::UnopenedPlaceholder(3)),
+            (r"\{2:5}", FormatError::UnopenedPlaceholder(5)),
+            (r"{2:5\}", FormatError::UnclosedPlaceholder(0)),
+            ("{2:5a}", FormatError::InvalidPadding(3, "5a".to_string())),
+            ("init {2:5", FormatError::UnclosedPlaceholder(5)),
+            ("init {2:5 end", FormatError::UnclosedPlaceholder(5)),
+            (
+                "{1|lower|shout}",
+                FormatError::UnknownFilter(9, "shout".to_string()),
+            ),
+            ("{1|lower", FormatError::UnclosedPlaceholder(0)),
+            (
+                "{1|slice:0:x}",
+                FormatError::InvalidFilterArgument(11, "x".to_string()),
+            ),
+            (
+                "{1|replace:a}",
+                FormatError::InvalidFilterArguments(3, "replace".to_string()),
+            ),
+            (r"{1|replace:a:b\", FormatError::UnclosedPlaceholder(0)),
+            ("{?:a}", FormatError::MissingCondition(0)),
+            ("a{?1: {1}", FormatError::UnclosedPlaceholder(1)),
+            ("{1?a", FormatError::UnclosedPlaceholder(0)),
+            ("{1+a}", FormatError::InvalidOperand(3, "a".to_string())),
+            ("{1*}", FormatError::InvalidOperand(3, String::new())),
+            ("a{1/0}", FormatError::DivisionByZero(4)),
         ];
 
         while let Some((format, err)) = format_error.pop() {
             assert_eq!(Formatter::new(format), Err(err));
         }
     }
+
+    #[test]
+    fn test_arithmetic_errors() {
+        let formatter = Formatter::new("a{1+1}").unwrap();
+        assert_eq!(
+            formatter.format(vec!["0", "one"]),
+            Err(FormatError::NotANumber(1, "one".to_string()))
+        );
+        assert_eq!(
+            formatter.format(vec!["0", "1.5"]),
+            Err(FormatError::NotANumber(1, "1.5".to_string()))
+        );
+        assert_eq!(
+            Formatter::new("{1*2}")
+                .unwrap()
+                .format(vec!["0", "9223372036854775807"]),
+            Err(FormatError::ArithmeticOverflow(
+                0,
+                "9223372036854775807".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_separators() {
+        assert_eq!(Formatter::new("{1}.txt").unwrap().separators(), 0);
+        assert_eq!(
+            Formatter::new("a/{1?b/c}{?1:/{1|replace:-:/}}")
+                .unwrap()
+                .separators(),
+            4
+        );
+    }
 }
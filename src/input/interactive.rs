@@ -0,0 +1,124 @@
+use std::collections::HashSet;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+enum Answer {
+    Yes,
+    No,
+    All,
+    Quit,
+    Edit,
+}
+
+/// Asks for each pair of `(input, output)` whether it should be renamed, and
+/// returns the confirmed pairs with their possibly corrected outputs. Reaching
+/// the end of `reader` is the same as quitting.
+pub fn confirm(
+    pairs: Vec<(String, String)>,
+    reader: &mut impl BufRead,
+    writer: &mut impl Write,
+) -> io::Result<Vec<(String, String)>> {
+    let mut confirmed = Vec::with_capacity(pairs.len());
+    let mut outputs = HashSet::new();
+    let mut pairs = pairs.into_iter();
+    while let Some((input, mut output)) = pairs.next() {
+        if input == output {
+            confirmed.push((input, output));
+            continue;
+        }
+        let answer = loop {
+            let collision = if outputs.contains(output.as_str()) {
+                " (already an output)"
+            } else if Path::new(output.as_str()).symlink_metadata().is_ok() {
+                " (exists)"
+            } else {
+                ""
+            };
+            write!(
+                writer,
+                "'{input}' -> '{output}'{collision} [y]es/[n]o/[a]ll/[q]uit/[e]dit? "
+            )?;
+            writer.flush()?;
+            match read_answer(reader)? {
+                Some(Answer::Edit) => {
+                    write!(writer, "output: ")?;
+                    writer.flush()?;
+                    let mut line = String::new();
+                    if reader.read_line(&mut line)? == 0 {
+                        break Answer::Quit;
+                    }
+                    let line = line.trim_end_matches(['\r', '\n']);
+                    if !line.is_empty() {
+                        output = line.to_string();
+                    }
+                }
+                Some(answer) => break answer,
+                None => writeln!(writer, "unknown answer")?,
+            }
+        };
+        match answer {
+            Answer::Yes => {
+                outputs.insert(output.clone());
+                confirmed.push((input, output));
+            }
+            Answer::All => {
+                confirmed.push((input, output));
+                confirmed.extend(pairs);
+                break;
+            }
+            Answer::Quit => break,
+            Answer::No | Answer::Edit => {}
+        }
+    }
+    Ok(confirmed)
+}
+
+/// Reads an answer, where `Ok(None)` means that it is not recognized.
+fn read_answer(reader: &mut impl BufRead) -> io::Result<Option<Answer>> {
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(Some(Answer::Quit));
+    }
+    Ok(match line.trim().to_lowercase().as_str() {
+        "y" | "yes" => Some(Answer::Yes),
+        "n" | "no" => Some(Answer::No),
+        "a" | "all" => Some(Answer::All),
+        "q" | "quit" => Some(Answer::Quit),
+        "e" | "edit" => Some(Answer::Edit),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pairs;
+
+    #[test]
+    fn test_confirm() {
+        let mut reader = "y\nn\nx\ne\nedited\ny\nq\n".as_bytes();
+        let mut writer = Vec::new();
+        let confirmed = confirm(
+            pairs(&[("a", "1"), ("b", "2"), ("c", "3"), ("d", "4")]),
+            &mut reader,
+            &mut writer,
+        )
+        .unwrap();
+        assert_eq!(confirmed, pairs(&[("a", "1"), ("c", "edited")]));
+        let prompts = String::from_utf8(writer).unwrap();
+        assert!(prompts.contains("'c' -> 'edited' [y]es"));
+        assert!(prompts.contains("unknown answer"));
+    }
+
+    #[test]
+    fn test_confirm_all() {
+        let mut reader = "a\n".as_bytes();
+        let confirmed = confirm(
+            pairs(&[("a", "1"), ("b", "1")]),
+            &mut reader,
+            &mut io::sink(),
+        )
+        .unwrap();
+        assert_eq!(confirmed, pairs(&[("a", "1"), ("b", "1")]));
+    }
+}
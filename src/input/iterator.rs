@@ -2,22 +2,15 @@ use crate::errors::{FormatError, SourceError};
 use crate::input::{Formatter, SortOrder, Source};
 use anyhow::Result;
 use regex::Regex;
+use std::cmp::Reverse;
 use std::fs;
 use std::iter::{IntoIterator, Iterator};
 use std::path::Path;
 use std::vec::IntoIter;
-use walkdir::IntoIter as WalkIter;
 use walkdir::WalkDir;
 
-#[allow(clippy::large_enum_variant)]
 pub enum InputIterator {
     VectorIterator(IntoIter<(String, String)>),
-    DirectoryIterator {
-        formatter: Formatter,
-        re: Regex,
-        preserve_extension: bool,
-        iter: WalkIter,
-    },
 }
 
 impl InputIterator {
@@ -47,7 +40,7 @@ impl InputIterator {
             });
             for (i, input) in inputs.into_iter().enumerate() {
                 let index = (i + 1).to_string();
-                let mut output = formatter.format(vec![input.as_str(), index.as_str()]);
+                let mut output = formatter.format(vec![input.as_str(), index.as_str()])?;
                 if preserve_extension && let Some(extension) = Path::new(input.as_str()).extension()
                 {
                     output.push('.');
@@ -60,53 +53,89 @@ impl InputIterator {
 
         if let Source::Regex(re, depth, max_depth) = source {
             let max_depth = max_depth.unwrap_or(depth);
-            return Ok(Self::DirectoryIterator {
-                formatter,
-                re,
-                preserve_extension,
-                iter: WalkDir::new(".")
-                    .min_depth(if depth > max_depth { max_depth } else { depth })
-                    .max_depth(max_depth)
-                    .into_iter(),
-            });
+            return Ok(Self::VectorIterator(
+                walk(&formatter, &re, preserve_extension, depth, max_depth)?.into_iter(),
+            ));
         }
 
         Err(SourceError::new(String::from("unknown source")).into())
     }
 }
 
+/// Matches all entries under the working directory before anything is renamed,
+/// so that renamed directories neither break the walk nor let moved files be
+/// matched twice.
+fn walk(
+    formatter: &Formatter,
+    re: &Regex,
+    preserve_extension: bool,
+    depth: usize,
+    max_depth: usize,
+) -> Result<Vec<(String, String)>, FormatError> {
+    match_entries(
+        walk_entries(depth, max_depth).as_slice(),
+        formatter,
+        re,
+        preserve_extension,
+    )
+}
+
+/// Lists the entries under the working directory from `depth` to `max_depth`
+/// along with their depth, ordered by name. Git directories are skipped along
+/// with their contents, which must never be renamed.
+pub fn walk_entries(depth: usize, max_depth: usize) -> Vec<(usize, String)> {
+    WalkDir::new(".")
+        .min_depth(if depth > max_depth { max_depth } else { depth })
+        .max_depth(max_depth)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            let input = path.strip_prefix("./").unwrap_or(path).to_string_lossy();
+            (entry.depth(), input.to_string())
+        })
+        .collect()
+}
+
+/// Formats the outputs of the `entries` listed by `walk_entries` which match
+/// `re`. Entries are ordered deepest-first, then by name, so that the contents
+/// of a directory are renamed before the path of the directory changes.
+pub fn match_entries(
+    entries: &[(usize, String)],
+    formatter: &Formatter,
+    re: &Regex,
+    preserve_extension: bool,
+) -> Result<Vec<(String, String)>, FormatError> {
+    let mut entries = entries
+        .iter()
+        .filter_map(|(depth, input)| {
+            let captures = re.captures(input.as_str())?;
+            let mut output = match formatter.format(captures) {
+                Ok(output) => output,
+                Err(e) => return Some(Err(e)),
+            };
+            if preserve_extension && let Some(extension) = Path::new(input.as_str()).extension() {
+                output.push('.');
+                output.push_str(extension.to_str().unwrap_or_default());
+            }
+            Some(Ok((*depth, input.clone(), output)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|(depth, _, _)| Reverse(*depth));
+    Ok(entries
+        .into_iter()
+        .map(|(_, input, output)| (input, output))
+        .collect())
+}
+
 impl Iterator for InputIterator {
     type Item = (String, String);
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
             Self::VectorIterator(iter) => iter.next(),
-            Self::DirectoryIterator {
-                formatter,
-                re,
-                preserve_extension,
-                iter,
-            } => {
-                for entry in iter {
-                    let Ok(entry) = entry else {
-                        continue;
-                    };
-                    let path = entry.path();
-                    let input = path.strip_prefix("./").unwrap_or(path).to_string_lossy();
-                    let Some(captures) = re.captures(input.as_ref()) else {
-                        continue;
-                    };
-                    let mut output = formatter.format(captures);
-                    if *preserve_extension
-                        && let Some(extension) = Path::new(input.as_ref()).extension()
-                    {
-                        output.push('.');
-                        output.push_str(extension.to_str().unwrap_or_default());
-                    }
-                    return Some((input.to_string(), output));
-                }
-                None
-            }
         }
     }
 }
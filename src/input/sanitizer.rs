@@ -0,0 +1,336 @@
+use crate::errors::{InvalidOutput, SanitizeError};
+use std::path::{self, Component, Path};
+
+/// Characters which are not allowed in filenames on Windows, besides control
+/// characters.
+const WINDOWS_CHARS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names which cannot be used as a filename on Windows, even with an
+/// extension.
+const WINDOWS_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Platforms on which outputs must be valid filenames.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Policy {
+    /// Rejects control characters.
+    Posix,
+    /// Rejects control characters, `<>:"/\|?*`, trailing dots and spaces, and
+    /// reserved device names such as `CON` or `aux.txt`.
+    Windows,
+    /// Rejects what is invalid on Windows, and leading hyphens and spaces.
+    Portable,
+}
+
+/// Checks the filenames of outputs according to a policy, and replaces their
+/// invalid characters if a replacement is set.
+#[derive(Debug, PartialEq)]
+pub struct Sanitizer {
+    policy: Policy,
+    replacement: Option<String>,
+    /// Number of separators the output format adds to the directories of each
+    /// input, if outputs are formatted.
+    separators: Option<usize>,
+}
+
+impl Sanitizer {
+    pub fn new(policy: Policy, replacement: Option<String>) -> Result<Self, SanitizeError> {
+        let sanitizer = Self {
+            policy,
+            replacement: None,
+            separators: None,
+        };
+        // A separator would split the filenames it replaces characters of.
+        if let Some(replacement) = replacement.as_deref()
+            && !replacement.is_empty()
+            && let Some(reason) = replacement
+                .chars()
+                .find(|ch| path::is_separator(*ch))
+                .map(|ch| format!("contains '{ch}'"))
+                .or_else(|| sanitizer.check(replacement))
+        {
+            return Err(SanitizeError::InvalidReplacement(
+                replacement.to_string(),
+                reason,
+            ));
+        }
+        Ok(Self {
+            policy,
+            replacement,
+            separators: None,
+        })
+    }
+
+    /// Rejects the outputs which have more directories than their input and
+    /// the `separators` of the output format, i.e. which a captured separator
+    /// would silently move into a new directory.
+    pub fn limit_separators(mut self, separators: usize) -> Self {
+        self.separators = Some(separators);
+        self
+    }
+
+    /// Sanitizes the outputs of `pairs` of `(input, output)`, or fails with all
+    /// the outputs which are invalid.
+    pub fn sanitize_pairs(
+        &self,
+        pairs: Vec<(String, String)>,
+    ) -> Result<Vec<(String, String)>, SanitizeError> {
+        let mut sanitized = Vec::with_capacity(pairs.len());
+        let mut invalid = Vec::new();
+        for (input, output) in pairs {
+            if let Some(separators) = self.separators
+                && depth(output.as_str()) > depth(input.as_str()) + separators
+            {
+                invalid.push(InvalidOutput {
+                    input,
+                    output,
+                    reason: String::from(
+                        "has more directories than its input and the output format",
+                    ),
+                });
+                continue;
+            }
+            match self.sanitize(output.as_str()) {
+                Ok(output) => sanitized.push((input, output)),
+                Err(reason) => invalid.push(InvalidOutput {
+                    input,
+                    output,
+                    reason,
+                }),
+            }
+        }
+        if !invalid.is_empty() {
+            return Err(SanitizeError::InvalidOutputs(invalid));
+        }
+        Ok(sanitized)
+    }
+
+    /// Sanitizes each filename of the path `output`, keeping its separators, or
+    /// returns the reason why it is invalid.
+    pub fn sanitize(&self, output: &str) -> Result<String, String> {
+        map_names(output, |name, _| {
+            self.sanitize_name(name)
+                .map_err(|reason| format!("name '{name}' {reason}"))
+        })
+    }
+
+    fn sanitize_name(&self, name: &str) -> Result<String, String> {
+        let Some(reason) = self.check(name) else {
+            return Ok(name.to_string());
+        };
+        let Some(replacement) = self.replacement.as_deref() else {
+            return Err(reason);
+        };
+        let rewritten = self.rewrite(name, replacement);
+        if rewritten.is_empty() {
+            return Err(String::from("is empty once sanitized"));
+        }
+        match self.check(rewritten.as_str()) {
+            Some(reason) => Err(reason),
+            None => Ok(rewritten),
+        }
+    }
+
+    /// Reason why `name` is not a valid filename, if any.
+    fn check(&self, name: &str) -> Option<String> {
+        if let Some(ch) = name.chars().find(|ch| self.is_invalid(*ch)) {
+            return Some(if ch.is_control() {
+                format!("contains control character U+{:04X}", ch as u32)
+            } else {
+                format!("contains '{ch}'")
+            });
+        }
+        if self.policy == Policy::Posix {
+            return None;
+        }
+        if let Some(last) = name
+            .chars()
+            .next_back()
+            .filter(|ch| matches!(ch, '.' | ' '))
+        {
+            return Some(format!("ends with '{last}'"));
+        }
+        if is_reserved(name) {
+            return Some(String::from("is a reserved name on Windows"));
+        }
+        if self.policy == Policy::Portable
+            && let Some(first) = name.chars().next().filter(|ch| matches!(ch, '-' | ' '))
+        {
+            return Some(format!("starts with '{first}'"));
+        }
+        None
+    }
+
+    fn rewrite(&self, name: &str, replacement: &str) -> String {
+        let mut rewritten: String = name
+            .chars()
+            .map(|ch| {
+                if self.is_invalid(ch) {
+                    replacement.to_string()
+                } else {
+                    ch.to_string()
+                }
+            })
+            .collect();
+        if self.policy == Policy::Posix {
+            return rewritten;
+        }
+        let trimmed = rewritten.trim_end_matches(['.', ' ']);
+        let count = rewritten.len() - trimmed.len();
+        rewritten = format!("{trimmed}{}", replacement.repeat(count));
+        if is_reserved(rewritten.as_str()) {
+            let stem = rewritten.find('.').unwrap_or(rewritten.len());
+            rewritten.insert_str(stem, replacement);
+        }
+        if self.policy == Policy::Portable {
+            let trimmed = rewritten.trim_start_matches(['-', ' ']);
+            let count = rewritten.len() - trimmed.len();
+            rewritten = format!("{}{trimmed}", replacement.repeat(count));
+        }
+        rewritten
+    }
+
+    fn is_invalid(&self, ch: char) -> bool {
+        ch.is_control() || (self.policy != Policy::Posix && WINDOWS_CHARS.contains(&ch))
+    }
+}
+
+/// Number of filenames of the path `path`.
+fn depth(path: &str) -> usize {
+    Path::new(path)
+        .components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .count()
+}
+
+/// Maps each filename of the path `output` with `map`, which is told whether
+/// it is the last one, keeping the separators, root and prefix of the path.
+pub(crate) fn map_names(
+    output: &str,
+    mut map: impl FnMut(&str, bool) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut mapped = String::with_capacity(output.len());
+    let mut rest = output;
+    loop {
+        let end = rest.find(path::is_separator).unwrap_or(rest.len());
+        let name = &rest[..end];
+        let is_last = end == rest.len();
+        let is_prefix = cfg!(windows) && mapped.is_empty() && name.ends_with(':');
+        if matches!(name, "" | "." | "..") || is_prefix {
+            mapped.push_str(name);
+        } else {
+            mapped.push_str(map(name, is_last)?.as_str());
+        }
+        let Some(separator) = rest[end..].chars().next() else {
+            break;
+        };
+        mapped.push(separator);
+        rest = &rest[end + separator.len_utf8()..];
+    }
+    Ok(mapped)
+}
+
+fn is_reserved(name: &str) -> bool {
+    let stem = name.split('.').next().unwrap_or_default();
+    WINDOWS_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check() {
+        let windows = Sanitizer::new(Policy::Windows, None).unwrap();
+        assert_eq!(windows.sanitize("dir/a.txt"), Ok(String::from("dir/a.txt")));
+        assert_eq!(
+            windows.sanitize("a?.txt"),
+            Err(String::from("name 'a?.txt' contains '?'"))
+        );
+        assert_eq!(
+            windows.sanitize("dir./a"),
+            Err(String::from("name 'dir.' ends with '.'"))
+        );
+        assert_eq!(
+            windows.sanitize("aux.txt"),
+            Err(String::from("name 'aux.txt' is a reserved name on Windows"))
+        );
+        assert!(windows.sanitize("auxiliary.txt").is_ok());
+        let posix = Sanitizer::new(Policy::Posix, None).unwrap();
+        assert_eq!(posix.sanitize("a?b:c. "), Ok(String::from("a?b:c. ")));
+        assert_eq!(
+            posix.sanitize("a\tb"),
+            Err(String::from(
+                "name 'a\tb' contains control character U+0009"
+            ))
+        );
+        let portable = Sanitizer::new(Policy::Portable, None).unwrap();
+        assert_eq!(
+            portable.sanitize("-a"),
+            Err(String::from("name '-a' starts with '-'"))
+        );
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let windows = Sanitizer::new(Policy::Windows, Some(String::from("_"))).unwrap();
+        assert_eq!(
+            windows.sanitize("dir/a:b?.. "),
+            Ok(String::from("dir/a_b____"))
+        );
+        assert_eq!(windows.sanitize("CON.txt"), Ok(String::from("CON_.txt")));
+        let windows = Sanitizer::new(Policy::Windows, Some(String::new())).unwrap();
+        assert_eq!(windows.sanitize("a<b>. "), Ok(String::from("ab")));
+        assert_eq!(
+            windows.sanitize("nul"),
+            Err(String::from("name 'nul' is a reserved name on Windows"))
+        );
+        assert_eq!(
+            windows.sanitize("??"),
+            Err(String::from("name '??' is empty once sanitized"))
+        );
+        let portable = Sanitizer::new(Policy::Portable, Some(String::from("_"))).unwrap();
+        assert_eq!(portable.sanitize("--a"), Ok(String::from("__a")));
+        assert_eq!(
+            Sanitizer::new(Policy::Windows, Some(String::from("?"))),
+            Err(SanitizeError::InvalidReplacement(
+                String::from("?"),
+                String::from("contains '?'")
+            ))
+        );
+        assert_eq!(
+            Sanitizer::new(Policy::Posix, Some(String::from("/"))),
+            Err(SanitizeError::InvalidReplacement(
+                String::from("/"),
+                String::from("contains '/'")
+            ))
+        );
+    }
+
+    #[test]
+    fn test_limit_separators() {
+        let posix = Sanitizer::new(Policy::Posix, None)
+            .unwrap()
+            .limit_separators(1);
+        let pairs = vec![
+            (String::from("s1/a.txt"), String::from("s1/a.md")),
+            (String::from("a.txt"), String::from("b/a.md")),
+        ];
+        assert_eq!(posix.sanitize_pairs(pairs.clone()), Ok(pairs));
+        assert_eq!(
+            posix.sanitize_pairs(vec![(
+                String::from("s1/a.txt"),
+                String::from("b/s1/c/a.md")
+            )]),
+            Err(SanitizeError::InvalidOutputs(vec![InvalidOutput {
+                input: String::from("s1/a.txt"),
+                output: String::from("b/s1/c/a.md"),
+                reason: String::from("has more directories than its input and the output format"),
+            }]))
+        );
+    }
+}
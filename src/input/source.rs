@@ -31,11 +31,17 @@ impl Source {
         ))
     }
 
+    /// Reads the map of `input -> output` in `filename`, where files are
+    /// renamed before the directories containing them.
     pub fn new_map(filename: &Path) -> Result<Self> {
         let contents = fs::read_to_string(filename)?;
-        Ok(Self::Map(serde_json::from_str(contents.as_str()).map(
-            |map: HashMap<String, String>| map.into_iter().collect(),
-        )?))
+        let map: HashMap<String, String> = serde_json::from_str(contents.as_str())?;
+        let mut pairs: Vec<(String, String)> = map.into_iter().collect();
+        pairs.sort_by(|(a, _), (b, _)| {
+            let depth = |path: &str| Path::new(path).components().count();
+            depth(b).cmp(&depth(a)).then_with(|| a.cmp(b))
+        });
+        Ok(Self::Map(pairs))
     }
 
     pub fn new_sort(order: cli::Order) -> Result<Self> {
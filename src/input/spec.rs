@@ -0,0 +1,233 @@
+use crate::errors::FormatError;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Radix {
+    /// Lowercase hexadecimal.
+    Hex,
+    /// Uppercase hexadecimal.
+    UpperHex,
+    Octal,
+    Binary,
+}
+
+/// Format spec of a placeholder of the form `[[FILL]ALIGN][+][0][WIDTH][.PRECISION][TYPE]`,
+/// e.g. `{1:*^10}` or `{1:+08.2}`. A bare width such as `{1:3}` only pads
+/// non-negative integers with `0`.
+#[derive(Debug, PartialEq)]
+pub struct Spec {
+    fill: char,
+    align: Option<Align>,
+    sign: bool,
+    zero: bool,
+    width: Option<usize>,
+    precision: Option<usize>,
+    radix: Option<Radix>,
+}
+
+impl Spec {
+    /// Parses the spec `text` which starts at `position`.
+    pub fn parse(position: usize, text: &str) -> Result<Self, FormatError> {
+        let invalid = || FormatError::InvalidPadding(position, text.to_string());
+        let chars: Vec<char> = text.chars().collect();
+        let align = |ch: Option<&char>| match ch {
+            Some('<') => Some(Align::Left),
+            Some('^') => Some(Align::Center),
+            Some('>') => Some(Align::Right),
+            _ => None,
+        };
+        let mut spec = Self {
+            fill: ' ',
+            align: None,
+            sign: false,
+            zero: false,
+            width: None,
+            precision: None,
+            radix: None,
+        };
+        let mut rest = chars.as_slice();
+        if let Some(align) = align(chars.get(1)) {
+            spec.fill = chars[0];
+            spec.align = Some(align);
+            rest = &rest[2..];
+        } else if let Some(align) = align(chars.first()) {
+            spec.align = Some(align);
+            rest = &rest[1..];
+        }
+        if let [ch, tail @ ..] = rest
+            && *ch == '+'
+        {
+            spec.sign = true;
+            rest = tail;
+        }
+        if let [ch, tail @ ..] = rest
+            && *ch == '0'
+        {
+            spec.zero = true;
+            rest = tail;
+        }
+        let (width, tail) = digits(rest);
+        spec.width = width;
+        rest = tail;
+        if let [ch, tail @ ..] = rest
+            && *ch == '.'
+        {
+            let (precision, tail) = digits(tail);
+            spec.precision = Some(precision.ok_or_else(invalid)?);
+            rest = tail;
+        }
+        spec.radix = match rest {
+            [] => None,
+            ['x'] => Some(Radix::Hex),
+            ['X'] => Some(Radix::UpperHex),
+            ['o'] => Some(Radix::Octal),
+            ['b'] => Some(Radix::Binary),
+            _ => return Err(invalid()),
+        };
+        if spec.radix.is_some() && spec.precision.is_some() {
+            return Err(invalid());
+        }
+        Ok(spec)
+    }
+
+    /// Formats `value`. Numeric options only apply to values which are
+    /// numbers, i.e. integers for the radix, and other values are only aligned.
+    pub fn apply(&self, value: String) -> String {
+        let Some(width) = self.width else {
+            return self.number(value.as_str()).unwrap_or(value);
+        };
+        if self.is_bare() {
+            return match value.parse::<usize>() {
+                Ok(number) => format!("{number:0>width$}"),
+                Err(_) => value,
+            };
+        }
+        let (value, align) = match self.number(value.as_str()) {
+            Some(number) if self.zero => {
+                let (sign, digits) = number.split_at(usize::from(number.starts_with(['-', '+'])));
+                let width = width.saturating_sub(sign.len());
+                return format!("{sign}{digits:0>width$}");
+            }
+            Some(number) => (number, self.align.unwrap_or(Align::Right)),
+            None if self.zero => return value,
+            None => (value, self.align.unwrap_or(Align::Left)),
+        };
+        let padding = width.saturating_sub(value.chars().count());
+        let (left, right) = match align {
+            Align::Left => (0, padding),
+            Align::Center => (padding / 2, padding - padding / 2),
+            Align::Right => (padding, 0),
+        };
+        let fill = |count: usize| self.fill.to_string().repeat(count);
+        format!("{}{value}{}", fill(left), fill(right))
+    }
+
+    /// Whether the spec only has a width, which keeps the former padding.
+    fn is_bare(&self) -> bool {
+        self.align.is_none()
+            && !self.sign
+            && !self.zero
+            && self.precision.is_none()
+            && self.radix.is_none()
+    }
+
+    /// Formats `value` as a number with its sign, or `None` if it is not a
+    /// number.
+    fn number(&self, value: &str) -> Option<String> {
+        let (negative, magnitude) = if let Some(radix) = self.radix {
+            let number: i64 = value.parse().ok()?;
+            let magnitude = number.unsigned_abs();
+            let magnitude = match radix {
+                Radix::Hex => format!("{magnitude:x}"),
+                Radix::UpperHex => format!("{magnitude:X}"),
+                Radix::Octal => format!("{magnitude:o}"),
+                Radix::Binary => format!("{magnitude:b}"),
+            };
+            (number < 0, magnitude)
+        } else if let Ok(number) = value.parse::<i64>()
+            && self.precision.is_none()
+        {
+            (number < 0, number.unsigned_abs().to_string())
+        } else {
+            let number: f64 = value
+                .parse()
+                .ok()
+                .filter(|number: &f64| number.is_finite())?;
+            let magnitude = match self.precision {
+                Some(precision) => format!("{:.precision$}", number.abs()),
+                None => value.trim_start_matches(['-', '+']).to_string(),
+            };
+            (number.is_sign_negative(), magnitude)
+        };
+        let sign = if negative {
+            "-"
+        } else if self.sign {
+            "+"
+        } else {
+            ""
+        };
+        Some(format!("{sign}{magnitude}"))
+    }
+}
+
+/// Parses the leading digits of `chars`, if any, and returns the rest.
+fn digits(chars: &[char]) -> (Option<usize>, &[char]) {
+    let end = chars
+        .iter()
+        .position(|ch| !ch.is_ascii_digit())
+        .unwrap_or(chars.len());
+    let number = chars[..end].iter().collect::<String>().parse().ok();
+    (number, &chars[end..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(spec: &str, value: &str) -> String {
+        Spec::parse(0, spec).unwrap().apply(value.to_string())
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(apply("3", "7"), "007");
+        assert_eq!(apply("3", "-7"), "-7");
+        assert_eq!(apply("3", "a"), "a");
+        assert_eq!(apply("03", "-7"), "-07");
+        assert_eq!(apply("03", "a"), "a");
+        assert_eq!(apply("06.2", "-3.14159"), "-03.14");
+        assert_eq!(apply("+", "7"), "+7");
+        assert_eq!(apply("+", "-7"), "-7");
+        assert_eq!(apply("+.1", "2"), "+2.0");
+        assert_eq!(apply(".2", "a"), "a");
+        assert_eq!(apply("x", "255"), "ff");
+        assert_eq!(apply("X", "-255"), "-FF");
+        assert_eq!(apply("o", "8"), "10");
+        assert_eq!(apply("08b", "5"), "00000101");
+        assert_eq!(apply("x", "1.5"), "1.5");
+        assert_eq!(apply("5", "ab"), "ab");
+        assert_eq!(apply("<5", "ab"), "ab   ");
+        assert_eq!(apply(">5", "ab"), "   ab");
+        assert_eq!(apply("*^5", "ab"), "*ab**");
+        assert_eq!(apply("-^6", "été"), "-été--");
+        assert_eq!(apply("+5", "3"), "   +3");
+        assert_eq!(apply("_<+5", "3"), "+3___");
+        assert_eq!(apply("<2", "abc"), "abc");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for spec in ["5a", ".", "1.x", ".2x", "<<<", "x5"] {
+            assert_eq!(
+                Spec::parse(3, spec),
+                Err(FormatError::InvalidPadding(3, spec.to_string()))
+            );
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use crate::errors::{LengthError, LongName};
+use crate::input::map_names;
+use std::path::Path;
+
+/// Maximum length in bytes of a filename on most filesystems.
+pub const NAME_MAX: usize = 255;
+
+/// How filenames longer than `NAME_MAX` bytes are shortened.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Truncation {
+    /// Cuts the stem.
+    Plain,
+    /// Cuts the stem and appends `…`.
+    Ellipsis,
+    /// Cuts the stem and appends `~` and a hash of the whole filename, so that
+    /// filenames which only differ after the cut are kept unique.
+    Hash,
+}
+
+/// Shortens the filenames of the outputs of `pairs` of `(input, output)` which
+/// are longer than `NAME_MAX` bytes using `truncation`, or fails with all such
+/// outputs if it is not set.
+pub fn check_lengths(
+    pairs: Vec<(String, String)>,
+    truncation: Option<Truncation>,
+) -> Result<Vec<(String, String)>, LengthError> {
+    let mut checked = Vec::with_capacity(pairs.len());
+    let mut long = Vec::new();
+    for (input, output) in pairs {
+        let length = longest_name(output.as_str());
+        if length <= NAME_MAX {
+            checked.push((input, output));
+            continue;
+        }
+        match truncation {
+            Some(truncation) => {
+                let output = truncate(output.as_str(), truncation);
+                checked.push((input, output));
+            }
+            None => long.push(LongName {
+                input,
+                output,
+                length,
+            }),
+        }
+    }
+    if !long.is_empty() {
+        return Err(LengthError(long));
+    }
+    Ok(checked)
+}
+
+/// Shortens each filename of the path `output` which is longer than
+/// `NAME_MAX` bytes, keeping the extension of the last one.
+pub fn truncate(output: &str, truncation: Truncation) -> String {
+    map_names(output, |name, is_last| {
+        Ok(truncate_name(name, is_last, truncation))
+    })
+    .unwrap_or_else(|_| output.to_string())
+}
+
+fn longest_name(output: &str) -> usize {
+    let mut longest = 0;
+    let _ = map_names(output, |name, _| {
+        longest = longest.max(name.len());
+        Ok(String::new())
+    });
+    longest
+}
+
+/// Cuts the stem of `name` on a character boundary so that it fits in
+/// `NAME_MAX` bytes along with the suffix of `truncation` and the extension,
+/// which is only kept if `keep_extension` is set and if it fits itself.
+fn truncate_name(name: &str, keep_extension: bool, truncation: Truncation) -> String {
+    if name.len() <= NAME_MAX {
+        return name.to_string();
+    }
+    let suffix = match truncation {
+        Truncation::Plain => String::new(),
+        Truncation::Ellipsis => String::from("…"),
+        Truncation::Hash => format!("~{}", hash(name)),
+    };
+    let extension = Path::new(name)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .filter(|extension| keep_extension && extension.len() + suffix.len() < NAME_MAX)
+        .map(|extension| format!(".{extension}"))
+        .unwrap_or_default();
+    let stem = &name[..name.len() - extension.len()];
+    let mut end = NAME_MAX - extension.len() - suffix.len();
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{suffix}{extension}", &stem[..end])
+}
+
+/// 32-bit FNV-1a hash of `name`.
+fn hash(name: &str) -> String {
+    let hash = name.bytes().fold(0x811c_9dc5_u32, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    });
+    format!("{hash:08x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_name() {
+        let name = format!("{}é.mkv", "a".repeat(250));
+        let truncated = truncate_name(name.as_str(), true, Truncation::Plain);
+        // 'é' does not fit entirely, so it is dropped.
+        assert_eq!(truncated, format!("{}.mkv", "a".repeat(250)));
+        let truncated = truncate_name(name.as_str(), true, Truncation::Ellipsis);
+        assert_eq!(truncated, format!("{}….mkv", "a".repeat(248)));
+        let truncated = truncate_name(name.as_str(), true, Truncation::Hash);
+        assert_eq!(truncated.len(), NAME_MAX);
+        assert!(truncated.ends_with(format!("~{}.mkv", hash(name.as_str())).as_str()));
+        let truncated = truncate_name(name.as_str(), false, Truncation::Plain);
+        assert_eq!(truncated.len(), NAME_MAX);
+        assert_eq!(
+            truncate_name("short.mkv", true, Truncation::Hash),
+            "short.mkv"
+        );
+    }
+
+    #[test]
+    fn test_check_lengths() {
+        let long = "a".repeat(300);
+        let pairs = vec![
+            (String::from("a"), format!("{long}/b.txt")),
+            (String::from("b"), String::from("b.txt")),
+        ];
+        assert_eq!(
+            check_lengths(pairs.clone(), None),
+            Err(LengthError(vec![LongName {
+                input: String::from("a"),
+                output: format!("{long}/b.txt"),
+                length: 300,
+            }]))
+        );
+        let checked = check_lengths(pairs, Some(Truncation::Plain)).unwrap();
+        assert_eq!(checked[0].1, format!("{}/b.txt", "a".repeat(NAME_MAX)));
+        assert_eq!(checked[1].1, "b.txt");
+    }
+}
@@ -1,21 +1,88 @@
 pub mod cli;
+pub mod tui;
 
 pub mod input {
+    mod editor;
+    mod filter;
     mod formatter;
+    mod interactive;
     mod iterator;
     mod provider;
+    mod sanitizer;
     mod separator;
     mod source;
+    mod spec;
+    mod truncate;
+    pub use self::editor::*;
+    pub use self::filter::*;
     pub use self::formatter::*;
+    pub use self::interactive::*;
     pub use self::iterator::*;
     pub use self::provider::*;
+    pub use self::sanitizer::*;
     pub use self::separator::*;
     pub use self::source::*;
+    pub use self::spec::*;
+    pub use self::truncate::*;
 }
 
 pub mod errors {
+    mod conflict;
+    mod containment;
+    mod edit;
     mod format;
+    mod git;
+    mod history;
+    mod journal;
+    mod length;
+    mod sanitize;
     mod source;
+    pub use self::conflict::*;
+    pub use self::containment::*;
+    pub use self::edit::*;
     pub use self::format::*;
+    pub use self::git::*;
+    pub use self::history::*;
+    pub use self::journal::*;
+    pub use self::length::*;
+    pub use self::sanitize::*;
     pub use self::source::*;
 }
+
+pub mod rename {
+    mod conflict;
+    mod containment;
+    mod git;
+    mod history;
+    mod journal;
+    mod noreplace;
+    mod plan;
+    mod refs;
+    mod strategy;
+    mod symlinks;
+    mod transaction;
+    mod transfer;
+    pub use self::conflict::*;
+    pub use self::containment::*;
+    pub use self::git::*;
+    pub use self::history::*;
+    pub use self::journal::*;
+    pub use self::noreplace::*;
+    pub use self::plan::*;
+    pub use self::refs::*;
+    pub use self::strategy::*;
+    pub use self::symlinks::*;
+    pub use self::transaction::*;
+    pub use self::transfer::*;
+}
+
+#[cfg(test)]
+mod tests {
+    /// Pairs of `(input, output)` shared by the tests of the modules.
+    pub(crate) fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(input, output)| (input.to_string(), output.to_string()))
+            .collect()
+    }
+}
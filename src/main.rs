@@ -1,16 +1,26 @@
 use anyhow::{Result, ensure};
 use colored::{self, Colorize};
 use is_terminal::IsTerminal;
-use nomino::cli::{Cli, Order};
-use nomino::errors::SourceError;
-use nomino::input::{Formatter, InputIterator, Source};
+use nomino::cli::{Cli, Command, Order};
+use nomino::errors::{GitError, HistoryError, SourceError};
+use nomino::input::{
+    Formatter, InputIterator, Sanitizer, Source, check_lengths, confirm, edit, list_paths,
+};
+use nomino::rename::{
+    ConflictStrategy, History, Journal, Mode, Operation, Plan, Recovery, Repository, Resolution,
+    Retarget, Rewrite, Transaction, check_conflicts, check_containment, find_retargets,
+    find_rewrites, find_symlinks, forward, journal_path,
+};
+use nomino::tui;
 use prettytable::{Table, format, row};
 use serde_json::map::Map;
 use serde_json::value::Value;
 use std::env::{args, set_current_dir};
 use std::fs;
+use std::io::{self, ErrorKind};
 use std::path::Path;
 use std::process::exit;
+use std::time::{Duration, UNIX_EPOCH};
 
 fn read_source(
     regex: Option<(&str, Option<usize>, Option<usize>)>,
@@ -44,49 +54,104 @@ fn read_output(output: Option<&str>) -> Result<Option<Formatter>> {
     Ok(Some(Formatter::new(output.unwrap())?))
 }
 
+/// A file which has been skipped or whose conflict has been resolved.
+struct Note {
+    input: String,
+    output: String,
+    message: String,
+    skipped: bool,
+}
+
+struct Report {
+    /// Map of `output -> input` of the renamed files.
+    map: Option<Map<String, Value>>,
+    /// Pairs of `(input, output)` in the order they have been renamed.
+    renamed: Vec<(String, String)>,
+    notes: Vec<Note>,
+    with_err: bool,
+}
+
+enum Outcome {
+    /// The file has been moved to the output, with a note if its conflict has
+    /// been resolved.
+    Renamed(String, Option<String>),
+    Skipped,
+    Failed,
+}
+
+/// Moves `source` to `output` and resolves its conflicts using `strategy`. An
+/// output is never replaced unless the strategy says so, even if it has been
+/// created by another process right before moving.
+fn rename_file(
+    transaction: &mut Transaction,
+    strategy: ConflictStrategy,
+    source: &str,
+    output: &str,
+    mkdir: bool,
+) -> io::Result<Outcome> {
+    loop {
+        let resolution = strategy.resolve(Path::new(source), Path::new(output), |path| {
+            transaction.exists(path)
+        });
+        let (target, replace, message) = match resolution {
+            Resolution::Proceed => (output.to_string(), false, None),
+            Resolution::Rename(path) => (
+                path.to_string_lossy().to_string(),
+                false,
+                Some(format!("renamed from '{output}'")),
+            ),
+            Resolution::Backup(backup) => {
+                match transaction.rename_noreplace(Path::new(output), backup.as_path()) {
+                    Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+                    Err(e) => return Err(e),
+                    Ok(_) => (
+                        output.to_string(),
+                        false,
+                        Some(format!("backed up to '{}'", backup.display())),
+                    ),
+                }
+            }
+            Resolution::Overwrite => (output.to_string(), true, Some(String::from("overwritten"))),
+            Resolution::Skip => return Ok(Outcome::Skipped),
+            Resolution::Fail => return Ok(Outcome::Failed),
+        };
+        if mkdir {
+            let _ = transaction.create_parent_dirs(Path::new(target.as_str()));
+        }
+        let result = if replace {
+            transaction.transfer(Path::new(source), Path::new(target.as_str()))
+        } else {
+            transaction.transfer_noreplace(Path::new(source), Path::new(target.as_str()))
+        };
+        match result {
+            Ok(_) => return Ok(Outcome::Renamed(target, message)),
+            Err(e) if !replace && e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
 fn rename_files(
-    input_iter: InputIterator,
-    test_mode: bool,
+    plan: Plan,
+    mut transaction: Transaction,
     need_map: bool,
-    overwrite: bool,
+    strategy: ConflictStrategy,
     mkdir: bool,
-) -> (Option<Map<String, Value>>, bool) {
-    let mut map = if need_map { Some(Map::new()) } else { None };
-    let mut is_renamed = true;
+    atomic: bool,
+) -> Report {
+    // Inputs which are at their output, either renamed or left in place.
+    let mut inputs = Vec::new();
+    // Pairs of `(input, output)` in the order they have been renamed, where
+    // outputs are relative to the directories containing them at the time.
+    let mut renamed = Vec::new();
+    let mut notes = Vec::new();
     let mut with_err = false;
-    for (input, mut output) in input_iter {
-        if input.as_str() == output.as_str() {
-            map.as_mut().map(|m| m.insert(output, Value::String(input)));
-            continue;
-        }
-        let mut file_path_buf;
-        let mut file_path = Path::new(output.as_str());
-        if !overwrite {
-            while file_path.exists() {
-                file_path_buf = file_path
-                    .with_file_name(
-                        (String::from("_")
-                            + file_path
-                                .file_name()
-                                .and_then(|name| name.to_str())
-                                .unwrap_or_default())
-                        .as_str(),
-                    )
-                    .to_path_buf();
-                file_path = file_path_buf.as_path();
-                output = file_path.to_string_lossy().to_string();
-            }
-        }
-        if mkdir {
-            let _ = file_path
-                .parent()
-                .and_then(|parent| fs::create_dir_all(parent).ok());
-        }
-        if !test_mode {
-            match fs::rename(input.as_str(), file_path) {
-                Ok(_) => is_renamed = true,
-                Err(e) => {
-                    is_renamed = false;
+    for operation in plan {
+        let (input, source, mut output) = match operation {
+            Operation::Stash { input, temporary } => {
+                if let Err(e) =
+                    transaction.rename_noreplace(Path::new(&input), Path::new(&temporary))
+                {
                     with_err = true;
                     eprintln!(
                         "[{}] unable to rename '{}': {}",
@@ -94,77 +159,660 @@ fn rename_files(
                         input.as_str(),
                         e
                     );
+                    if atomic {
+                        break;
+                    }
+                }
+                continue;
+            }
+            Operation::Rename {
+                input,
+                source,
+                output,
+            } => (input, source, output),
+        };
+        if input.as_str() == output.as_str() {
+            inputs.push(input);
+            continue;
+        }
+        let (is_renamed, message) = match rename_file(
+            &mut transaction,
+            strategy,
+            source.as_str(),
+            output.as_str(),
+            mkdir,
+        ) {
+            Ok(Outcome::Renamed(target, message)) => {
+                output = target;
+                (true, message)
+            }
+            Ok(Outcome::Skipped) => {
+                notes.push(Note {
+                    input,
+                    output,
+                    message: String::from("skipped, output exists"),
+                    skipped: true,
+                });
+                continue;
+            }
+            Ok(Outcome::Failed) => {
+                with_err = true;
+                eprintln!(
+                    "[{}] unable to rename '{}': '{}' already exists",
+                    "error".red().bold(),
+                    input.as_str(),
+                    output.as_str(),
+                );
+                break;
+            }
+            Err(e) => {
+                with_err = true;
+                eprintln!(
+                    "[{}] unable to rename '{}': {}",
+                    "error".red().bold(),
+                    input.as_str(),
+                    e
+                );
+                if atomic {
+                    break;
                 }
+                (false, None)
             }
+        };
+        if is_renamed && let Some(message) = message {
+            notes.push(Note {
+                input: input.clone(),
+                output: output.clone(),
+                message,
+                skipped: false,
+            });
         }
-        if is_renamed && need_map {
-            map.as_mut().map(|m| m.insert(output, Value::String(input)));
+        if is_renamed {
+            inputs.push(input.clone());
+            renamed.push((input, output));
         }
-        is_renamed = true;
     }
-    (map, with_err)
+    if atomic && with_err && !transaction.is_empty() {
+        if rollback(transaction) {
+            eprintln!(
+                "[{}] all renamed files have been restored",
+                "rollback".yellow().bold()
+            );
+        }
+        inputs.clear();
+        renamed.clear();
+        notes.clear();
+    } else {
+        let journal = transaction.journal().map(Path::to_path_buf);
+        if let Err(e) = transaction.commit() {
+            with_err = true;
+            eprintln!(
+                "[{}] unable to remove journal '{}': {}",
+                "error".red().bold(),
+                journal.unwrap_or_default().display(),
+                e
+            );
+        }
+    }
+    // Outputs are reported at their final path, i.e. once the directories
+    // containing them have been renamed as well.
+    let map = need_map.then(|| {
+        inputs
+            .into_iter()
+            .map(|input| {
+                let output = forward(renamed.as_slice(), Path::new(input.as_str()));
+                (output.to_string_lossy().to_string(), Value::String(input))
+            })
+            .collect()
+    });
+    for note in notes.iter_mut().filter(|note| !note.skipped) {
+        note.output = forward(renamed.as_slice(), Path::new(note.input.as_str()))
+            .to_string_lossy()
+            .to_string();
+    }
+    Report {
+        map,
+        renamed,
+        notes,
+        with_err,
+    }
 }
 
-fn print_map_table(map: Map<String, Value>) {
+/// Reverts `transaction` and reports its errors. Returns `true` on success.
+fn rollback(transaction: Transaction) -> bool {
+    let errors = transaction.rollback();
+    for (path, e) in errors.iter() {
+        eprintln!(
+            "[{}] unable to roll back '{}': {}",
+            "error".red().bold(),
+            path.display(),
+            e
+        );
+    }
+    errors.is_empty()
+}
+
+fn recover(revert: bool, need_map: bool) -> Result<Report> {
+    let path = journal_path()?;
+    let recovery = Recovery::new(path.as_path(), Journal::read(path.as_path())?)?;
+    let journal = Journal::open(path.as_path())?;
+    if revert {
+        let map = need_map.then(|| {
+            recovery
+                .renamed
+                .iter()
+                .map(|(source, target, _)| (source.clone(), Value::String(target.clone())))
+                .collect()
+        });
+        let transaction = Transaction::resume(recovery.renamed, recovery.directories, journal);
+        return Ok(Report {
+            map,
+            renamed: Vec::new(),
+            notes: Vec::new(),
+            with_err: !rollback(transaction),
+        });
+    }
+    Ok(rename_files(
+        Plan::from(recovery.remaining),
+        Transaction::resume(recovery.renamed, recovery.directories, journal).mode(recovery.mode),
+        need_map,
+        recovery.on_conflict,
+        recovery.mkdir,
+        false,
+    ))
+}
+
+fn print_report(map: Map<String, Value>, notes: Vec<Note>) {
+    if map.is_empty() && notes.is_empty() {
+        return;
+    }
+    colored::control::set_override(std::io::stdout().is_terminal());
     let mut table = Table::new();
     table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
-    table.set_titles(row![Fc => "Input", "Output"]);
-    map.into_iter()
+    let with_notes = !notes.is_empty();
+    if with_notes {
+        table.set_titles(row![Fc => "Input", "Output", "Status"]);
+    } else {
+        table.set_titles(row![Fc => "Input", "Output"]);
+    }
+    let mut rows: Vec<(String, String, String)> = map
+        .into_iter()
+        .filter_map(|(output, input)| match input {
+            Value::String(input) => Some((input, output, String::new())),
+            _ => None,
+        })
+        .collect();
+    for note in notes {
+        if note.skipped {
+            rows.push((note.input, note.output, note.message));
+        } else if let Some(row) = rows
+            .iter_mut()
+            .find(|(_, output, _)| *output == note.output)
+        {
+            row.2 = note.message;
+        }
+    }
+    rows.sort_by(|a, b| a.1.cmp(&b.1));
+    rows.into_iter()
         .enumerate()
-        .for_each(|(i, (output, input))| {
-            if let Value::String(input) = input {
-                if i % 2 == 0 {
-                    table.add_row(row![input.as_str().normal(), output.as_str().normal()]);
-                } else {
-                    table.add_row(row![Fm => input.as_str(), output.as_str()]);
+        .for_each(|(i, (input, output, status))| {
+            match (with_notes, i % 2 == 0) {
+                (false, true) => table.add_row(row![input.normal(), output.normal()]),
+                (false, false) => table.add_row(row![Fm => input, output]),
+                (true, true) => {
+                    table.add_row(row![input.normal(), output.normal(), status.normal()])
                 }
+                (true, false) => table.add_row(row![Fm => input, output, status]),
+            };
+        });
+    table.printstd();
+}
+
+/// Pairs of `(input, output)` of the files renamed according to `map`.
+fn renamed_pairs(map: &Map<String, Value>) -> Vec<(String, String)> {
+    map.iter()
+        .filter_map(|(output, input)| {
+            input
+                .as_str()
+                .filter(|input| *input != output.as_str())
+                .map(|input| (input.to_string(), output.clone()))
+        })
+        .collect()
+}
+
+fn record_history(
+    pairs: Vec<(String, String)>,
+    rewrites: Vec<Rewrite>,
+    retargets: Vec<Retarget>,
+) -> Result<()> {
+    if pairs.is_empty() {
+        return Ok(());
+    }
+    let mut history = History::open()?;
+    history.record(pairs, rewrites, retargets);
+    history.save()
+}
+
+/// Rewrites the references in text files, or reverts them if `revert` is set.
+/// Files which cannot be rewritten are reported and dropped. Returns `true` on
+/// success.
+fn write_rewrites(rewrites: &mut Vec<Rewrite>, revert: bool) -> bool {
+    let count = rewrites.len();
+    rewrites.retain_mut(|rewrite| {
+        let file = rewrite.file.clone();
+        let result = rewrite.write(Path::new(file.as_str()), revert);
+        if let Err(e) = result.as_ref() {
+            eprintln!(
+                "[{}] unable to rewrite references in '{}': {}",
+                "error".red().bold(),
+                file,
+                e
+            );
+        }
+        result.is_ok()
+    });
+    rewrites.len() == count
+}
+
+/// Retargets symlinks, or reverts them if `revert` is set. Symlinks which
+/// cannot be retargeted are reported and dropped. Returns `true` on success.
+fn write_retargets(retargets: &mut Vec<Retarget>, revert: bool) -> bool {
+    let count = retargets.len();
+    retargets.retain(|retarget| {
+        let result = retarget.write(revert);
+        if let Err(e) = result.as_ref() {
+            eprintln!(
+                "[{}] unable to retarget symlink '{}': {}",
+                "error".red().bold(),
+                retarget.link,
+                e
+            );
+        }
+        result.is_ok()
+    });
+    retargets.len() == count
+}
+
+fn print_retargets(retargets: &[Retarget]) {
+    if retargets.is_empty() {
+        return;
+    }
+    colored::control::set_override(std::io::stdout().is_terminal());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row![Fc => "Symlink", "Target", "Retargeted"]);
+    retargets.iter().enumerate().for_each(|(i, retarget)| {
+        if i % 2 == 0 {
+            table.add_row(row![retarget.link, retarget.old, retarget.new]);
+        } else {
+            table.add_row(row![Fm => retarget.link, retarget.old, retarget.new]);
+        }
+    });
+    table.printstd();
+}
+
+fn print_rewrites(rewrites: &[Rewrite]) {
+    if rewrites.is_empty() {
+        return;
+    }
+    colored::control::set_override(std::io::stdout().is_terminal());
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row![Fc => "File", "Line", "Reference", "Rewritten"]);
+    rewrites
+        .iter()
+        .flat_map(|rewrite| rewrite.edits.iter().map(move |edit| (rewrite, edit)))
+        .enumerate()
+        .for_each(|(i, (rewrite, edit))| {
+            if i % 2 == 0 {
+                table.add_row(row![rewrite.file, edit.line, edit.old, edit.new]);
+            } else {
+                table.add_row(row![Fm => rewrite.file, edit.line, edit.old, edit.new]);
             }
         });
     table.printstd();
 }
 
+/// Reverts the last `count` runs, or performs them again if `redo` is set.
+fn undo(count: usize, force: bool, redo: bool, need_map: bool) -> Result<Report> {
+    let mut history = History::open()?;
+    let indices = if redo {
+        history.redoable(count)?
+    } else {
+        history.undoable(count)?
+    };
+    let mut renamed = need_map.then(Map::new);
+    let mut notes = Vec::new();
+    for i in indices {
+        let run = &mut history.runs[i];
+        let changes = run.changes();
+        if !force && !changes.is_empty() {
+            return Err(HistoryError::Changed(changes).into());
+        }
+        // References and symlinks are rewritten while the files are at their
+        // path after the run, i.e. before it is undone or after it is redone.
+        let mut with_err = !redo
+            && !(write_rewrites(&mut run.rewrites, true)
+                & write_retargets(&mut run.retargets, true));
+        let plan = Plan::new(run.pairs());
+        let transaction = Transaction::with_journal(Journal::start(
+            journal_path()?.as_path(),
+            ConflictStrategy::Prefix,
+            true,
+            Mode::Move,
+            &plan,
+        )?);
+        let report = rename_files(
+            plan,
+            transaction,
+            need_map,
+            ConflictStrategy::Prefix,
+            true,
+            true,
+        );
+        if report.with_err {
+            if !redo {
+                write_rewrites(&mut run.rewrites, false);
+                write_retargets(&mut run.retargets, false);
+            }
+            return Ok(Report {
+                map: renamed,
+                renamed: Vec::new(),
+                notes,
+                with_err: true,
+            });
+        }
+        if redo {
+            with_err = !(write_rewrites(&mut run.rewrites, false)
+                & write_retargets(&mut run.retargets, false));
+        }
+        run.toggle();
+        history.save()?;
+        if let Some((renamed, map)) = renamed.as_mut().zip(report.map) {
+            renamed.extend(map);
+        }
+        notes.extend(report.notes);
+        if with_err {
+            return Ok(Report {
+                map: renamed,
+                renamed: Vec::new(),
+                notes,
+                with_err,
+            });
+        }
+    }
+    Ok(Report {
+        map: renamed,
+        renamed: Vec::new(),
+        notes,
+        with_err: false,
+    })
+}
+
+fn print_history(history: History) {
+    let mut table = Table::new();
+    table.set_format(*format::consts::FORMAT_NO_LINESEP_WITH_TITLE);
+    table.set_titles(row![Fc => "#", "Date", "Files", "State"]);
+    let count = history.runs.len();
+    history.runs.into_iter().enumerate().for_each(|(i, run)| {
+        let date =
+            humantime::format_rfc3339_seconds(UNIX_EPOCH + Duration::from_secs(run.timestamp));
+        let state = if run.undone { "undone" } else { "done" };
+        let files = run.records.len();
+        if i % 2 == 0 {
+            table.add_row(row![count - i, date, files, state]);
+        } else {
+            table.add_row(row![Fm => count - i, date, files, state]);
+        }
+    });
+    colored::control::set_override(std::io::stdout().is_terminal());
+    table.printstd();
+}
+
 fn run_app() -> Result<bool> {
     let mut opts = Cli::parse();
     if let Some(cwd) = opts.directory {
         set_current_dir(cwd)?;
     }
 
+    if let Some(command) = opts.command.take() {
+        let report = match command {
+            Command::Recover { revert } => recover(revert, !opts.quiet)?,
+            Command::Undo { count, force } => undo(count, force, false, !opts.quiet)?,
+            Command::Redo { count, force } => undo(count, force, true, !opts.quiet)?,
+            Command::History => {
+                print_history(History::open()?);
+                return Ok(false);
+            }
+        };
+        if let Some(map) = report.map {
+            print_report(map, report.notes);
+        }
+        return Ok(report.with_err);
+    }
+
+    if opts.tui {
+        let output = opts.output.pop().unwrap_or_default();
+        let regex = opts.regex.take().or(opts.output.pop()).unwrap_or_default();
+        let Some((regex, output)) = tui::run(
+            regex,
+            output,
+            opts.depth,
+            opts.max_depth,
+            !opts.no_extension,
+        )?
+        else {
+            return Ok(false);
+        };
+        opts.regex = Some(regex);
+        opts.output = vec![output];
+    }
+
+    let mut sanitizer = opts
+        .sanitize
+        .map(|policy| Sanitizer::new(policy.into(), opts.sanitize_with.take()))
+        .transpose()?;
+    let with_source = opts.regex.is_some() || opts.sort.is_some() || opts.map.is_some();
+    let mut pairs: Vec<(String, String)> = if opts.edit && !with_source {
+        list_paths(opts.output.as_slice())?
+            .into_iter()
+            .map(|path| (path.clone(), path))
+            .collect()
+    } else {
+        ensure!(
+            !with_source || opts.output.len() <= 1,
+            "optional SOURCE must be used without setting regex, map or sort flags",
+        );
+        let output = opts.output.pop();
+        let pattern = opts.output.pop();
+        let formatter = read_output(output.as_deref())?;
+        // Captured separators must not move outputs into new directories, unless
+        // the outputs are edited by hand.
+        if let Some(formatter) = formatter.as_ref()
+            && !opts.edit
+            && !opts.interactive
+        {
+            sanitizer =
+                sanitizer.map(|sanitizer| sanitizer.limit_separators(formatter.separators()));
+        }
+        InputIterator::new(
+            read_source(
+                opts.regex
+                    .or(pattern)
+                    .as_deref()
+                    .map(|pattern| (pattern, opts.depth, opts.max_depth)),
+                opts.sort,
+                opts.map.as_deref(),
+            )?,
+            formatter,
+            !opts.no_extension,
+        )?
+        .collect()
+    };
+    if opts.edit {
+        pairs = edit(pairs)?;
+    }
+    if opts.interactive {
+        pairs = confirm(pairs, &mut io::stdin().lock(), &mut io::stdout())?;
+    }
+    if let Some(sanitizer) = sanitizer.as_ref() {
+        pairs = sanitizer.sanitize_pairs(pairs)?;
+    }
+    pairs = check_lengths(pairs, opts.truncate.map(Into::into))?;
+    if !opts.allow_outside {
+        check_containment(pairs.as_slice())?;
+    }
+    let mode = Mode::from(opts.mode);
+    // Edited outputs are free-form paths, so their directories are created.
+    let mkdir = opts.mkdir || opts.edit;
+    let repository = if opts.git {
+        ensure!(
+            mode == Mode::Move,
+            "'--git' can only be used in 'move' mode"
+        );
+        Repository::open()?
+    } else {
+        None
+    };
+    if let Some(repository) = repository.as_ref()
+        && !opts.force
+    {
+        let dirty = repository.dirty_outputs(pairs.as_slice());
+        if !dirty.is_empty() {
+            return Err(GitError::Dirty(dirty).into());
+        }
+    }
+    // Symlinks are found before renaming, as they may be renamed themselves.
+    let symlinks = if opts.retarget_symlinks {
+        ensure!(
+            mode == Mode::Move,
+            "'--retarget-symlinks' can only be used in 'move' mode"
+        );
+        find_symlinks(opts.symlink_root.as_slice())?
+    } else {
+        Vec::new()
+    };
+    // Existing outputs are left to the conflict strategy, if any, but several
+    // inputs sharing an output are always refused.
+    if !opts.allow_conflicts {
+        check_conflicts(
+            pairs.as_slice(),
+            opts.overwrite || opts.on_conflict.is_some(),
+            mode,
+        )?;
+    }
+    let strategy = match (opts.overwrite, opts.on_conflict) {
+        (true, _) => ConflictStrategy::Overwrite,
+        (_, Some(on_conflict)) => on_conflict.into(),
+        _ => ConflictStrategy::Prefix,
+    };
     ensure!(
-        (opts.regex.is_none() && opts.sort.is_none() && opts.map.is_none())
-            || opts.output.len() <= 1,
-        "optional SOURCE must be used without setting regex, map or sort flags",
+        !opts.atomic || !strategy.is_destructive(),
+        "'--atomic' cannot be used with a strategy which replaces existing outputs, as they could not be restored"
     );
-
-    let output = opts.output.pop();
-    let pattern = opts.output.pop();
-    let input_iter = InputIterator::new(
-        read_source(
-            opts.regex
-                .or(pattern)
-                .as_deref()
-                .map(|pattern| (pattern, opts.depth, opts.max_depth)),
-            opts.sort,
-            opts.map.as_deref(),
-        )?,
-        read_output(output.as_deref())?,
-        !opts.no_extension,
-    )?;
-    let (map, with_err) = rename_files(
-        input_iter,
-        opts.test,
-        !opts.quiet || opts.generate.is_some(),
-        opts.overwrite,
-        opts.mkdir,
+    let plan = match mode {
+        Mode::Move => Plan::new(pairs),
+        _ => Plan::sequential(pairs),
+    };
+    let transaction = if opts.test {
+        Transaction::simulated()
+    } else {
+        Transaction::with_journal(Journal::start(
+            journal_path()?.as_path(),
+            strategy,
+            mkdir,
+            mode,
+            &plan,
+        )?)
+        .verify(opts.verify)
+    }
+    .mode(mode);
+    let Report {
+        map,
+        renamed,
+        notes,
+        mut with_err,
+    } = rename_files(
+        plan,
+        transaction,
+        !opts.test || !opts.quiet || opts.generate.is_some(),
+        strategy,
+        mkdir,
+        opts.atomic,
     );
+    if !opts.test
+        && let Some(repository) = repository.as_ref()
+        && let Err(e) = repository.stage_renames(renamed.as_slice())
+    {
+        with_err = true;
+        eprintln!(
+            "[{}] unable to stage the renames: {}",
+            "error".red().bold(),
+            e
+        );
+    }
+    let mut rewrites = Vec::new();
+    if let Some(glob) = opts.update_refs.as_deref()
+        && !with_err
+        && let Some(map) = map.as_ref()
+    {
+        match find_rewrites(glob, renamed_pairs(map).as_slice(), !opts.test) {
+            Ok(found) => rewrites = found,
+            Err(e) => {
+                with_err = true;
+                eprintln!(
+                    "[{}] unable to find references: {}",
+                    "error".red().bold(),
+                    e
+                );
+            }
+        }
+        if !opts.test && !write_rewrites(&mut rewrites, false) {
+            with_err = true;
+        }
+    }
+    let mut retargets = Vec::new();
+    if opts.retarget_symlinks
+        && !with_err
+        && let Some(map) = map.as_ref()
+    {
+        match find_retargets(symlinks.as_slice(), renamed_pairs(map).as_slice()) {
+            Ok(found) => retargets = found,
+            Err(e) => {
+                with_err = true;
+                eprintln!(
+                    "[{}] unable to retarget symlinks: {}",
+                    "error".red().bold(),
+                    e
+                );
+            }
+        }
+        if !opts.test && !write_retargets(&mut retargets, false) {
+            with_err = true;
+        }
+    }
+    // Copies and links cannot be reverted by renaming them back, so only moves
+    // are recorded.
+    if !opts.test
+        && mode == Mode::Move
+        && let Err(e) = record_history(renamed, rewrites.clone(), retargets.clone())
+    {
+        eprintln!(
+            "[{}] unable to record the run in history: {}",
+            "warning".yellow().bold(),
+            e
+        );
+    }
     if let Some(map_file) = opts.generate {
         fs::write(
             map_file,
             serde_json::to_vec_pretty(map.as_ref().unwrap())?.as_slice(),
         )?;
     }
-    if let Some(map) = map.filter(|map| !opts.quiet && !map.is_empty()) {
-        colored::control::set_override(std::io::stdout().is_terminal());
-        print_map_table(map);
+    if let Some(map) = map.filter(|_| !opts.quiet) {
+        print_report(map, notes);
+        print_rewrites(rewrites.as_slice());
+        print_retargets(retargets.as_slice());
     }
     Ok(with_err)
 }
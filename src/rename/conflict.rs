@@ -0,0 +1,80 @@
+use crate::errors::{Conflict, ConflictError};
+use crate::rename::Mode;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Checks the whole list of `(input, output)` pairs before anything is renamed.
+/// Outputs shared by several inputs are always reported, while outputs which
+/// are already occupied by a file not moved away by another pair are only
+/// reported if `overwrite` is not set. Inputs only free their path if `mode`
+/// moves them.
+pub fn check_conflicts(
+    pairs: &[(String, String)],
+    overwrite: bool,
+    mode: Mode,
+) -> Result<(), ConflictError> {
+    let moved: HashSet<&str> = pairs
+        .iter()
+        .filter(|(input, output)| mode == Mode::Move && input != output)
+        .map(|(input, _)| input.as_str())
+        .collect();
+    let mut groups: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (input, output) in pairs {
+        groups
+            .entry(output.as_str())
+            .or_default()
+            .push(input.as_str());
+    }
+    let conflicts: Vec<Conflict> = groups
+        .into_iter()
+        .filter_map(|(output, inputs)| {
+            let exists = !overwrite
+                && !moved.contains(output)
+                && !inputs.contains(&output)
+                && Path::new(output).symlink_metadata().is_ok();
+            (inputs.len() > 1 || exists).then(|| Conflict {
+                output: output.to_string(),
+                inputs: inputs.into_iter().map(String::from).collect(),
+                exists,
+            })
+        })
+        .collect();
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(ConflictError::new(conflicts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pairs;
+
+    #[test]
+    fn test_no_conflicts() {
+        let pairs = pairs(&[("a", "b"), ("b", "a"), ("c", "c")]);
+        assert_eq!(check_conflicts(&pairs, false, Mode::Move), Ok(()));
+    }
+
+    #[test]
+    fn test_duplicate_outputs() {
+        let pairs = pairs(&[("a", "x"), ("b", "y"), ("c", "x"), ("y", "y")]);
+        let err = check_conflicts(&pairs, true, Mode::Move).unwrap_err();
+        assert_eq!(
+            err.conflicts(),
+            [
+                Conflict {
+                    output: "x".to_string(),
+                    inputs: vec!["a".to_string(), "c".to_string()],
+                    exists: false,
+                },
+                Conflict {
+                    output: "y".to_string(),
+                    inputs: vec!["b".to_string(), "y".to_string()],
+                    exists: false,
+                },
+            ]
+        );
+    }
+}
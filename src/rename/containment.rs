@@ -0,0 +1,92 @@
+use crate::errors::{ContainmentError, Outside};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Checks that the inputs and outputs of `pairs` of `(input, output)` which
+/// are renamed resolve under the working directory, once `..` components and
+/// symlinks to directories are resolved. The paths themselves are not followed,
+/// as symlinks are renamed rather than their targets.
+pub fn check_containment(pairs: &[(String, String)]) -> Result<()> {
+    let cwd = fs::canonicalize(env::current_dir()?)?;
+    let mut checked = HashSet::new();
+    let mut outside = Vec::new();
+    for path in pairs
+        .iter()
+        .filter(|(input, output)| input != output)
+        .flat_map(|(input, output)| [input, output])
+    {
+        if !checked.insert(path.as_str()) {
+            continue;
+        }
+        let resolved = resolve(cwd.join(path).as_path());
+        if resolved == cwd || !resolved.starts_with(cwd.as_path()) {
+            outside.push(Outside {
+                path: path.clone(),
+                resolved,
+            });
+        }
+    }
+    if !outside.is_empty() {
+        return Err(ContainmentError(outside).into());
+    }
+    Ok(())
+}
+
+/// Resolves the absolute `path` without following its last component: the
+/// longest existing ancestor of its parent is canonicalized, and the remaining
+/// components are resolved lexically.
+fn resolve(path: &Path) -> PathBuf {
+    let mut resolved = PathBuf::new();
+    let mut components = path.components();
+    let last = components.next_back();
+    for component in components {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                if let Ok(canonical) = fs::canonicalize(resolved.as_path()) {
+                    resolved = canonical;
+                }
+            }
+        }
+    }
+    match last {
+        Some(Component::ParentDir) => {
+            resolved.pop();
+        }
+        Some(Component::CurDir) | None => {}
+        Some(component) => resolved.push(component),
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(dir.path()).unwrap();
+        fs::create_dir(root.join("dir")).unwrap();
+        assert_eq!(
+            resolve(root.join("dir/../missing/./../file").as_path()),
+            root.join("file")
+        );
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink("/", root.join("up")).unwrap();
+            assert_eq!(
+                resolve(root.join("up/etc/file").as_path()),
+                Path::new("/etc/file")
+            );
+            assert_eq!(resolve(root.join("up").as_path()), root.join("up"));
+        }
+    }
+}
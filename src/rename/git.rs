@@ -0,0 +1,212 @@
+use crate::errors::GitError;
+use crate::rename::forward;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Object ID used by `git update-index` to remove an entry from the index.
+const NULL_OBJECT: &str = "0000000000000000000000000000000000000000";
+
+struct IndexEntry {
+    mode: String,
+    object: String,
+    path: String,
+}
+
+/// Snapshot of the index and status of the Git repository containing the
+/// working directory. Paths are relative to the root of the repository.
+pub struct Repository {
+    root: PathBuf,
+    /// Path of the working directory relative to the root.
+    prefix: PathBuf,
+    index: Vec<IndexEntry>,
+    dirty: HashSet<String>,
+}
+
+impl Repository {
+    /// Reads the repository containing the working directory, or returns
+    /// `None` if there is none.
+    pub fn open() -> Result<Option<Self>> {
+        let Ok(output) = Command::new("git")
+            .args(["rev-parse", "--show-toplevel", "--show-prefix"])
+            .stderr(Stdio::null())
+            .output()
+        else {
+            return Ok(None);
+        };
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let stdout = String::from_utf8_lossy(output.stdout.as_slice()).to_string();
+        let mut lines = stdout.lines();
+        let root = PathBuf::from(lines.next().unwrap_or_default());
+        let prefix = PathBuf::from(lines.next().unwrap_or_default());
+        let mut repository = Self {
+            root,
+            prefix,
+            index: Vec::new(),
+            dirty: HashSet::new(),
+        };
+        let index = repository.git(&["ls-files", "--stage", "-z"], None)?;
+        for line in index.split('\0').filter(|line| !line.is_empty()) {
+            let Some((info, path)) = line.split_once('\t') else {
+                continue;
+            };
+            let mut info = info.split(' ');
+            let (Some(mode), Some(object), Some(stage)) = (info.next(), info.next(), info.next())
+            else {
+                continue;
+            };
+            // Entries with merge conflicts cannot be moved in the index.
+            if stage != "0" {
+                repository.dirty.insert(path.to_string());
+                continue;
+            }
+            repository.index.push(IndexEntry {
+                mode: mode.to_string(),
+                object: object.to_string(),
+                path: path.to_string(),
+            });
+        }
+        let status = repository.git(
+            &["status", "--porcelain", "-z", "--untracked-files=all"],
+            None,
+        )?;
+        let mut entries = status.split('\0').filter(|entry| !entry.is_empty());
+        while let Some(entry) = entries.next() {
+            let (state, path) = entry.split_at(entry.len().min(3));
+            repository.dirty.insert(path.to_string());
+            // Renames and copies are followed by their original path.
+            if state.starts_with(['R', 'C']) {
+                entries.next();
+            }
+        }
+        Ok(Some(repository))
+    }
+
+    /// Returns the outputs of `pairs` of `(input, output)` which, or any file
+    /// under which, have uncommitted changes.
+    pub fn dirty_outputs(&self, pairs: &[(String, String)]) -> Vec<String> {
+        pairs
+            .iter()
+            .filter(|(input, output)| {
+                input != output
+                    && self.relative(output).is_some_and(|output| {
+                        self.dirty
+                            .iter()
+                            .any(|dirty| contains(output.as_str(), dirty.as_str()))
+                    })
+            })
+            .map(|(_, output)| output.clone())
+            .collect()
+    }
+
+    /// Moves the index entries under the inputs of `pairs` of `(input, output)`,
+    /// given in the order they have been renamed, to their final path, keeping
+    /// their staged contents as `git mv` does. The files themselves must have
+    /// been renamed already.
+    pub fn stage_renames(&self, pairs: &[(String, String)]) -> Result<()> {
+        let pairs: Vec<(String, String)> = pairs
+            .iter()
+            .filter_map(|(input, output)| self.relative(input).zip(self.relative(output)))
+            .collect();
+        let mut removed = String::new();
+        let mut added = String::new();
+        for entry in self.index.iter() {
+            // Entries under a renamed directory are moved along with it, even
+            // if they have been renamed themselves before.
+            let path = forward(pairs.as_slice(), Path::new(entry.path.as_str()))
+                .to_string_lossy()
+                .replace('\\', "/");
+            if path == entry.path {
+                continue;
+            }
+            removed.push_str(format!("0 {NULL_OBJECT}\t{}\0", entry.path).as_str());
+            added.push_str(format!("{} {}\t{path}\0", entry.mode, entry.object).as_str());
+        }
+        if removed.is_empty() {
+            return Ok(());
+        }
+        // All entries are removed first, so that cycles such as `a -> b, b -> a`
+        // do not remove the entries which have just been added.
+        removed.push_str(added.as_str());
+        self.git(&["update-index", "-z", "--index-info"], Some(removed))?;
+        Ok(())
+    }
+
+    /// Path relative to the root of the repository of `path` relative to the
+    /// working directory, or `None` if it is outside of the repository.
+    fn relative(&self, path: &str) -> Option<String> {
+        let path = Path::new(path);
+        let path = if path.is_absolute() {
+            path.strip_prefix(self.root.as_path()).ok()?.to_path_buf()
+        } else {
+            self.prefix.join(path)
+        };
+        let mut relative = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    if !relative.pop() {
+                        return None;
+                    }
+                }
+                Component::Normal(name) => relative.push(name),
+                Component::RootDir | Component::Prefix(_) => return None,
+            }
+        }
+        Some(relative.to_string_lossy().replace('\\', "/"))
+    }
+
+    fn git(&self, args: &[&str], stdin: Option<String>) -> Result<String> {
+        let mut child = Command::new("git")
+            .arg("-C")
+            .arg(self.root.as_path())
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        if let Some(stdin) = stdin
+            && let Some(mut pipe) = child.stdin.take()
+        {
+            pipe.write_all(stdin.as_bytes())?;
+        }
+        let Output {
+            status,
+            stdout,
+            stderr,
+        } = child.wait_with_output()?;
+        if !status.success() {
+            return Err(GitError::Command(format!(
+                "'git {}' failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(stderr.as_slice()).trim()
+            ))
+            .into());
+        }
+        Ok(String::from_utf8_lossy(stdout.as_slice()).to_string())
+    }
+}
+
+/// Checks whether `path` is `parent` itself or under it.
+fn contains(parent: &str, path: &str) -> bool {
+    path.strip_prefix(parent)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contains() {
+        assert!(contains("dir", "dir"));
+        assert!(contains("dir", "dir/file"));
+        assert!(!contains("dir", "directory"));
+        assert!(!contains("dir/file", "dir"));
+    }
+}
@@ -0,0 +1,408 @@
+use crate::errors::HistoryError;
+use crate::rename::{Retarget, Rewrite, forward, temporary_path};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable which overrides the directory the history is kept in.
+pub const STATE_DIR_ENV: &str = "NOMINO_STATE_DIR";
+
+/// Maximum number of runs kept in the history of a working directory, older
+/// runs are dropped first.
+pub const MAX_RUNS: usize = 100;
+
+/// Size and modification time of a file, used to detect whether it has been
+/// changed since it was renamed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Stamp {
+    pub len: u64,
+    pub modified: Option<u64>,
+}
+
+impl Stamp {
+    pub fn of(path: &Path) -> Option<Self> {
+        let metadata = path.symlink_metadata().ok()?;
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .and_then(|duration| u64::try_from(duration.as_nanos()).ok()),
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub input: String,
+    pub output: String,
+    /// Stamp of the file at its current path, i.e. its final path once the
+    /// directories containing it have been renamed too, unless undone.
+    pub stamp: Option<Stamp>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Run {
+    /// Seconds since the Unix epoch at which the run was recorded.
+    pub timestamp: u64,
+    /// Renames in the order they have been performed, where outputs are
+    /// relative to the directories containing them at the time.
+    pub records: Vec<Record>,
+    pub undone: bool,
+    /// References rewritten in text files after the files have been renamed.
+    #[serde(default)]
+    pub rewrites: Vec<Rewrite>,
+    /// Symlinks retargeted after the files have been renamed.
+    #[serde(default)]
+    pub retargets: Vec<Retarget>,
+}
+
+impl Run {
+    /// Returns the paths which prevent this run from being undone (or redone if
+    /// it is undone), i.e. files which have been modified or moved since.
+    pub fn changes(&self) -> Vec<String> {
+        let finals = self.finals();
+        let pairs: Vec<(&str, &str)> = self
+            .records
+            .iter()
+            .zip(finals.iter())
+            .map(|(record, last)| {
+                if self.undone {
+                    (record.input.as_str(), record.output.as_str())
+                } else {
+                    (last.as_str(), record.input.as_str())
+                }
+            })
+            .collect();
+        // Paths freed by this run itself, e.g. when two files have been swapped.
+        let currents: HashSet<&str> = pairs.iter().map(|(current, _)| *current).collect();
+        let mut changes = Vec::new();
+        for ((current, original), record) in pairs.into_iter().zip(self.records.iter()) {
+            if Stamp::of(Path::new(current)) != record.stamp {
+                changes.push(current.to_string());
+            }
+            if !currents.contains(original) && Path::new(original).symlink_metadata().is_ok() {
+                changes.push(original.to_string());
+            }
+        }
+        if !self.undone {
+            for rewrite in self.rewrites.iter() {
+                if Stamp::of(Path::new(rewrite.file.as_str())) != rewrite.stamp {
+                    changes.push(rewrite.file.clone());
+                }
+            }
+            for retarget in self.retargets.iter() {
+                if fs::read_link(retarget.link.as_str()).ok().as_deref()
+                    != Some(Path::new(retarget.new.as_str()))
+                {
+                    changes.push(retarget.link.clone());
+                }
+            }
+        }
+        changes
+    }
+
+    /// Pairs of `(input, output)` which revert this run in the reverse order
+    /// of its renames, or redo it in the same order if undone.
+    pub fn pairs(&self) -> Vec<(String, String)> {
+        if self.undone {
+            self.records
+                .iter()
+                .map(|record| (record.input.clone(), record.output.clone()))
+                .collect()
+        } else {
+            self.records
+                .iter()
+                .rev()
+                .map(|record| (record.output.clone(), record.input.clone()))
+                .collect()
+        }
+    }
+
+    /// Marks this run as undone or redone and refreshes the stamps of its files.
+    pub fn toggle(&mut self) {
+        self.undone = !self.undone;
+        self.refresh();
+    }
+
+    fn refresh(&mut self) {
+        let finals = self.finals();
+        for (record, last) in self.records.iter_mut().zip(finals) {
+            let current = if self.undone {
+                record.input.as_str()
+            } else {
+                last.as_str()
+            };
+            record.stamp = Stamp::of(Path::new(current));
+        }
+    }
+
+    /// Final path of each renamed file once the whole run is performed.
+    fn finals(&self) -> Vec<String> {
+        let renames: Vec<(&str, &str)> = self
+            .records
+            .iter()
+            .map(|record| (record.input.as_str(), record.output.as_str()))
+            .collect();
+        self.records
+            .iter()
+            .map(|record| {
+                forward(renames.as_slice(), Path::new(record.input.as_str()))
+                    .to_string_lossy()
+                    .to_string()
+            })
+            .collect()
+    }
+}
+
+/// Runs performed in a working directory, stored in the state directory of
+/// the user, e.g. `~/.local/state/nomino/history`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct History {
+    pub directory: PathBuf,
+    pub runs: Vec<Run>,
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+impl History {
+    /// Opens the history of the current working directory.
+    pub fn open() -> Result<Self> {
+        let directory = fs::canonicalize(env::current_dir()?)?;
+        let path = state_path("history", "json")?;
+        match fs::read_to_string(path.as_path()) {
+            Ok(contents) => {
+                let mut history: Self = serde_json::from_str(contents.as_str())?;
+                history.path = path;
+                Ok(history)
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(Self {
+                directory,
+                runs: Vec::new(),
+                path,
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Records a new run of `(input, output)` pairs in the order they have been
+    /// renamed, along with the references and symlinks it has rewritten. Undone
+    /// runs cannot be redone anymore afterwards.
+    pub fn record(
+        &mut self,
+        pairs: Vec<(String, String)>,
+        rewrites: Vec<Rewrite>,
+        retargets: Vec<Retarget>,
+    ) {
+        self.runs.retain(|run| !run.undone);
+        let mut run = Run {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            records: pairs
+                .into_iter()
+                .map(|(input, output)| Record {
+                    input,
+                    output,
+                    stamp: None,
+                })
+                .collect(),
+            undone: false,
+            rewrites,
+            retargets,
+        };
+        run.refresh();
+        self.runs.push(run);
+        if self.runs.len() > MAX_RUNS {
+            self.runs.drain(..self.runs.len() - MAX_RUNS);
+        }
+    }
+
+    /// Indices of the last `count` runs which can be undone, latest first.
+    pub fn undoable(&self, count: usize) -> Result<Vec<usize>, HistoryError> {
+        let indices: Vec<usize> = (0..self.runs.len())
+            .rev()
+            .filter(|&i| !self.runs[i].undone)
+            .take(count)
+            .collect();
+        if indices.is_empty() {
+            return Err(HistoryError::NothingToUndo);
+        }
+        Ok(indices)
+    }
+
+    /// Indices of the last `count` undone runs which can be redone, earliest
+    /// first.
+    pub fn redoable(&self, count: usize) -> Result<Vec<usize>, HistoryError> {
+        let undone = self.runs.iter().rev().take_while(|run| run.undone).count();
+        let indices: Vec<usize> = (self.runs.len() - undone..self.runs.len())
+            .take(count)
+            .collect();
+        if indices.is_empty() {
+            return Err(HistoryError::NothingToRedo);
+        }
+        Ok(indices)
+    }
+
+    /// Writes the history to a temporary file which then replaces the former
+    /// one, so that it is never left truncated, and removes the histories of
+    /// working directories which do not exist anymore.
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+            prune(parent);
+        }
+        let temporary = temporary_path(self.path.as_path());
+        let result = fs::File::create(temporary.as_path()).and_then(|mut file| {
+            file.write_all(serde_json::to_vec_pretty(self)?.as_slice())?;
+            file.sync_all()?;
+            fs::rename(temporary.as_path(), self.path.as_path())
+        });
+        if result.is_err() {
+            let _ = fs::remove_file(temporary.as_path());
+        }
+        Ok(result?)
+    }
+}
+
+/// Directory in which nomino keeps its state, e.g. `~/.local/state/nomino`,
+/// under the directory set by `NOMINO_STATE_DIR` if any.
+pub fn state_dir() -> Result<PathBuf, HistoryError> {
+    env::var_os(STATE_DIR_ENV)
+        .map(PathBuf::from)
+        .or_else(|| dirs::state_dir().or_else(dirs::data_local_dir))
+        .map(|dir| dir.join("nomino"))
+        .ok_or(HistoryError::NoStateDirectory)
+}
+
+/// Path of the state file of the current working directory in the `kind`
+/// directory of the state directory, e.g. `history/<hash>.json`.
+pub fn state_path(kind: &str, extension: &str) -> Result<PathBuf> {
+    let directory = fs::canonicalize(env::current_dir()?)?;
+    Ok(state_dir()?
+        .join(kind)
+        .join(format!("{}.{extension}", hash(directory.as_path()))))
+}
+
+/// Removes the histories stored in `dir` whose working directory has been
+/// removed, as they cannot be undone anymore.
+fn prune(dir: &Path) {
+    #[derive(Deserialize)]
+    struct Directory {
+        directory: PathBuf,
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().is_none_or(|extension| extension != "json") {
+            continue;
+        }
+        let Ok(contents) = fs::read_to_string(path.as_path()) else {
+            continue;
+        };
+        if let Ok(history) = serde_json::from_str::<Directory>(contents.as_str())
+            && !history.directory.is_dir()
+        {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// FNV-1a hash of `path`, which is stable across platforms and releases.
+fn hash(path: &Path) -> String {
+    let hash = path
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(undone: &[bool]) -> History {
+        History {
+            directory: PathBuf::new(),
+            runs: undone
+                .iter()
+                .map(|&undone| Run {
+                    timestamp: 0,
+                    records: Vec::new(),
+                    undone,
+                    rewrites: Vec::new(),
+                    retargets: Vec::new(),
+                })
+                .collect(),
+            path: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_undoable() {
+        assert_eq!(history(&[false, false, true]).undoable(5), Ok(vec![1, 0]));
+        assert_eq!(
+            history(&[true]).undoable(1),
+            Err(HistoryError::NothingToUndo)
+        );
+    }
+
+    #[test]
+    fn test_redoable() {
+        assert_eq!(history(&[false, true, true]).redoable(1), Ok(vec![1]));
+        assert_eq!(
+            history(&[true, false]).redoable(1),
+            Err(HistoryError::NothingToRedo)
+        );
+    }
+
+    #[test]
+    fn test_record_drops_undone_runs() {
+        let mut history = history(&[false, true]);
+        history.record(Vec::new(), Vec::new(), Vec::new());
+        assert_eq!(history.runs.len(), 2);
+        assert!(history.runs.iter().all(|run| !run.undone));
+    }
+
+    #[test]
+    fn test_record_caps_runs() {
+        let mut history = history(&[false; MAX_RUNS]);
+        history.runs[0].timestamp = 1;
+        history.record(Vec::new(), Vec::new(), Vec::new());
+        assert_eq!(history.runs.len(), MAX_RUNS);
+        assert_eq!(history.runs[0].timestamp, 0);
+    }
+
+    #[test]
+    fn test_save_prunes_removed_directories() {
+        let state = tempfile::tempdir().unwrap();
+        let removed = tempfile::tempdir().unwrap();
+        let mut stale = history(&[false]);
+        stale.directory = removed.path().to_path_buf();
+        stale.path = state.path().join("stale.json");
+        stale.save().unwrap();
+        removed.close().unwrap();
+        let mut current = history(&[false]);
+        current.directory = state.path().to_path_buf();
+        current.path = state.path().join("current.json");
+        current.save().unwrap();
+        let mut files: Vec<String> = fs::read_dir(state.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["current.json"]);
+    }
+}
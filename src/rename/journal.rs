@@ -0,0 +1,266 @@
+use crate::errors::JournalError;
+use crate::rename::{ConflictStrategy, Mode, Operation, Plan, state_path};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Path of the journal of the working directory, kept in the state directory
+/// alongside its history, e.g. `~/.local/state/nomino/journal/<hash>.journal`.
+pub fn journal_path() -> Result<PathBuf> {
+    state_path("journal", "journal")
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Entry {
+    /// Options of the batch, always the first entry.
+    Start {
+        on_conflict: ConflictStrategy,
+        mkdir: bool,
+        mode: Mode,
+    },
+    /// An operation of the batch, written before anything is renamed.
+    Plan(Operation),
+    /// A directory which is about to be created.
+    Mkdir { path: String },
+    /// A rename, or a copy or link in another mode, which is about to be
+    /// performed.
+    Begin {
+        source: String,
+        target: String,
+        mode: Mode,
+    },
+    /// Marks the last begun rename as completed.
+    End,
+    /// Marks the last completed rename as reverted.
+    Undo,
+}
+
+/// Append-only log of a batch, synced to disk before and after each rename so
+/// that an interrupted batch can be completed or reverted by `nomino recover`.
+pub struct Journal {
+    file: File,
+    path: PathBuf,
+}
+
+impl Journal {
+    /// Creates the journal of a new batch and writes its whole plan. Fails if
+    /// the journal of an interrupted batch already exists.
+    pub fn start(
+        path: &Path,
+        on_conflict: ConflictStrategy,
+        mkdir: bool,
+        mode: Mode,
+        plan: &Plan,
+    ) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .append(true)
+            .create_new(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                ErrorKind::AlreadyExists => JournalError::Stale(path.to_path_buf()).into(),
+                _ => anyhow::Error::from(e),
+            })?;
+        let mut journal = Self {
+            file,
+            path: path.to_path_buf(),
+        };
+        journal.write(&Entry::Start {
+            on_conflict,
+            mkdir,
+            mode,
+        })?;
+        for operation in plan.operations() {
+            journal.write(&Entry::Plan(operation.clone()))?;
+        }
+        Ok(journal)
+    }
+
+    /// Opens an existing journal to append the entries of its recovery.
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(|e| match e.kind() {
+                ErrorKind::NotFound => JournalError::NotFound(path.to_path_buf()).into(),
+                _ => anyhow::Error::from(e),
+            })?;
+        Ok(Self {
+            file,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Reads all entries of a journal. A truncated last line, e.g. due to a
+    /// power loss while it was written, is ignored.
+    pub fn read(path: &Path) -> Result<Vec<Entry>> {
+        let contents = fs::read_to_string(path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => JournalError::NotFound(path.to_path_buf()).into(),
+            _ => anyhow::Error::from(e),
+        })?;
+        let lines: Vec<&str> = contents.lines().collect();
+        let mut entries = Vec::with_capacity(lines.len());
+        for (i, line) in lines.iter().enumerate() {
+            match serde_json::from_str(line) {
+                Ok(entry) => entries.push(entry),
+                Err(_) if i + 1 == lines.len() && !contents.ends_with('\n') => break,
+                Err(_) => return Err(JournalError::Invalid(path.to_path_buf(), i + 1).into()),
+            }
+        }
+        Ok(entries)
+    }
+
+    pub fn write(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.write_all(line.as_slice())?;
+        self.file.sync_data()
+    }
+
+    pub fn path(&self) -> &Path {
+        self.path.as_path()
+    }
+
+    /// Removes the journal once its batch is either completed or reverted.
+    pub fn remove(self) -> io::Result<()> {
+        fs::remove_file(self.path)
+    }
+}
+
+/// State of an interrupted batch rebuilt from its journal.
+#[derive(Debug, PartialEq)]
+pub struct Recovery {
+    pub on_conflict: ConflictStrategy,
+    pub mkdir: bool,
+    pub mode: Mode,
+    /// Operations of the plan which have not been performed yet.
+    pub remaining: Vec<Operation>,
+    /// Performed renames of `(source, target, mode)` in their original order.
+    pub renamed: Vec<(String, String, Mode)>,
+    /// Directories created by the batch which still exist.
+    pub directories: Vec<String>,
+}
+
+impl Recovery {
+    pub fn new(path: &Path, entries: Vec<Entry>) -> Result<Self, JournalError> {
+        let mut entries = entries.into_iter();
+        let Some(Entry::Start {
+            on_conflict,
+            mkdir,
+            mode,
+        }) = entries.next()
+        else {
+            return Err(JournalError::Invalid(path.to_path_buf(), 1));
+        };
+        let mut plan = Vec::new();
+        let mut begun: Vec<(String, String, Mode, bool)> = Vec::new();
+        let mut directories = Vec::new();
+        for entry in entries {
+            match entry {
+                Entry::Plan(operation) => plan.push(operation),
+                Entry::Mkdir { path } => directories.push(path),
+                Entry::Begin {
+                    source,
+                    target,
+                    mode,
+                } => begun.push((source, target, mode, false)),
+                Entry::End => {
+                    if let Some(last) = begun.last_mut() {
+                        last.3 = true;
+                    }
+                }
+                Entry::Undo => {
+                    if let Some(position) = begun.iter().rposition(|(_, _, _, done)| *done) {
+                        begun.remove(position);
+                    }
+                }
+                Entry::Start { .. } => {}
+            }
+        }
+        // A rename without a completion marker might have been performed right
+        // before the process was killed, so the filesystem is checked instead.
+        // Copies and links are created under a temporary name first, so their
+        // target only exists once they are complete.
+        let renamed: Vec<(String, String, Mode)> = begun
+            .into_iter()
+            .filter(|(source, target, mode, done)| {
+                *done
+                    || (Path::new(target).symlink_metadata().is_ok()
+                        && (*mode != Mode::Move || Path::new(source).symlink_metadata().is_err()))
+            })
+            .map(|(source, target, mode, _)| (source, target, mode))
+            .collect();
+        let sources: HashSet<&str> = renamed
+            .iter()
+            .map(|(source, _, _)| source.as_str())
+            .collect();
+        let remaining = plan
+            .into_iter()
+            .filter(|operation| !sources.contains(operation.source()))
+            .collect();
+        directories.retain(|path| Path::new(path).is_dir());
+        directories.dedup();
+        Ok(Self {
+            on_conflict,
+            mkdir,
+            mode,
+            remaining,
+            renamed,
+            directories,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recovery() {
+        let entries = vec![
+            Entry::Start {
+                on_conflict: ConflictStrategy::Prefix,
+                mkdir: false,
+                mode: Mode::Move,
+            },
+            Entry::Plan(Operation::Rename {
+                input: "a".to_string(),
+                source: "a".to_string(),
+                output: "b".to_string(),
+            }),
+            Entry::Plan(Operation::Rename {
+                input: "c".to_string(),
+                source: "c".to_string(),
+                output: "d".to_string(),
+            }),
+            Entry::Begin {
+                source: "a".to_string(),
+                target: "b".to_string(),
+                mode: Mode::Move,
+            },
+            Entry::End,
+        ];
+        let recovery = Recovery::new(Path::new("journal"), entries).unwrap();
+        assert_eq!(
+            recovery,
+            Recovery {
+                on_conflict: ConflictStrategy::Prefix,
+                mkdir: false,
+                mode: Mode::Move,
+                remaining: vec![Operation::Rename {
+                    input: "c".to_string(),
+                    source: "c".to_string(),
+                    output: "d".to_string(),
+                }],
+                renamed: vec![("a".to_string(), "b".to_string(), Mode::Move)],
+                directories: Vec::new(),
+            }
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::Path;
+
+/// Renames `from` to `to` only if `to` does not exist, failing with
+/// `ErrorKind::AlreadyExists` otherwise. Unlike checking for `to` before
+/// renaming, no other process can create `to` in between on Linux, where
+/// `renameat2` with `RENAME_NOREPLACE` is used. If the filesystem does not
+/// support it, a hard link to `to` is created before `from` is removed.
+pub fn rename_noreplace(from: &Path, to: &Path) -> io::Result<()> {
+    #[cfg(target_os = "linux")]
+    match renameat2(from, to) {
+        Err(e)
+            if e.raw_os_error() == Some(libc::ENOSYS) || e.raw_os_error() == Some(libc::EINVAL) => {
+        }
+        result => return result,
+    }
+    #[cfg(unix)]
+    match fs::hard_link(from, to) {
+        Ok(_) => {
+            return fs::remove_file(from).inspect_err(|_| {
+                let _ = fs::remove_file(to);
+            });
+        }
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => return Err(e),
+        // Hard links are not supported for directories or by the filesystem.
+        Err(_) => {}
+    }
+    if to.symlink_metadata().is_ok() {
+        return Err(io::Error::from(ErrorKind::AlreadyExists));
+    }
+    fs::rename(from, to)
+}
+
+#[cfg(target_os = "linux")]
+fn renameat2(from: &Path, to: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let from = CString::new(from.as_os_str().as_bytes())?;
+    let to = CString::new(to.as_os_str().as_bytes())?;
+    // SAFETY: both paths are valid NUL-terminated strings which outlive the call.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_renameat2,
+            libc::AT_FDCWD,
+            from.as_ptr(),
+            libc::AT_FDCWD,
+            to.as_ptr(),
+            libc::RENAME_NOREPLACE,
+        )
+    };
+    if result == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rename_noreplace() {
+        let dir = tempfile::tempdir().unwrap();
+        let (a, b, c) = (
+            dir.path().join("a"),
+            dir.path().join("b"),
+            dir.path().join("c"),
+        );
+        fs::write(a.as_path(), "a").unwrap();
+        fs::write(b.as_path(), "b").unwrap();
+
+        let err = rename_noreplace(a.as_path(), b.as_path()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(b.as_path()).unwrap(), "b");
+
+        rename_noreplace(a.as_path(), c.as_path()).unwrap();
+        assert!(!a.exists());
+        assert_eq!(fs::read_to_string(c.as_path()).unwrap(), "a");
+    }
+}
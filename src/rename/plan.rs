@@ -0,0 +1,276 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// Moves `input` aside to a free `temporary` name to break a cycle.
+    Stash { input: String, temporary: String },
+    /// Renames `source` to `output`, where `source` is either `input` itself or
+    /// the temporary name it has been stashed to.
+    Rename {
+        input: String,
+        source: String,
+        output: String,
+    },
+}
+
+impl Operation {
+    /// Path of the file which is moved by this operation.
+    pub fn source(&self) -> &str {
+        match self {
+            Self::Stash { input, .. } => input.as_str(),
+            Self::Rename { source, .. } => source.as_str(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Plan(Vec<Operation>);
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Unvisited,
+    Visiting,
+    Visited,
+}
+
+impl Plan {
+    /// Orders `pairs` of `(input, output)` so that every output is freed by its
+    /// own rename before another input is moved to it, e.g. `1 -> 2, 2 -> 3` is
+    /// executed as `2 -> 3, 1 -> 2`. Cycles such as `a -> b, b -> a` are broken
+    /// by stashing one input to a temporary name.
+    pub fn new(pairs: Vec<(String, String)>) -> Self {
+        let indices: HashMap<&str, usize> = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (input, _))| (input.as_str(), i))
+            .collect();
+        // The rename which must be executed before the rename at the same index,
+        // i.e. the one which moves away the file currently occupying its output.
+        let dependencies: Vec<Option<usize>> = pairs
+            .iter()
+            .enumerate()
+            .map(|(i, (_, output))| {
+                indices
+                    .get(output.as_str())
+                    .copied()
+                    .filter(|&dependency| dependency != i)
+            })
+            .collect();
+        let mut reserved = HashSet::new();
+        let mut states = vec![State::Unvisited; pairs.len()];
+        let mut order = Vec::with_capacity(pairs.len());
+        for start in 0..pairs.len() {
+            let mut stack = Vec::new();
+            let mut current = Some(start);
+            while let Some(i) = current.filter(|&i| states[i] == State::Unvisited) {
+                states[i] = State::Visiting;
+                stack.push(i);
+                current = dependencies[i];
+            }
+            if let Some(i) = current.filter(|&i| states[i] == State::Visiting) {
+                let position = stack.iter().position(|&j| j == i).unwrap_or_default();
+                let cycle = stack.split_off(position);
+                let input = pairs[i].0.as_str();
+                let temporary = temporary_name(input, &mut reserved);
+                order.push(Operation::Stash {
+                    input: input.to_string(),
+                    temporary: temporary.clone(),
+                });
+                cycle.iter().skip(1).rev().for_each(|&j| {
+                    order.push(Operation::Rename {
+                        input: pairs[j].0.clone(),
+                        source: pairs[j].0.clone(),
+                        output: pairs[j].1.clone(),
+                    })
+                });
+                order.push(Operation::Rename {
+                    input: input.to_string(),
+                    source: temporary,
+                    output: pairs[i].1.clone(),
+                });
+                cycle.into_iter().for_each(|j| states[j] = State::Visited);
+            }
+            stack.into_iter().rev().for_each(|j| {
+                states[j] = State::Visited;
+                order.push(Operation::Rename {
+                    input: pairs[j].0.clone(),
+                    source: pairs[j].0.clone(),
+                    output: pairs[j].1.clone(),
+                });
+            });
+        }
+        Self(order)
+    }
+
+    /// Keeps `pairs` in their order, for modes which leave every input in
+    /// place, so that no output is ever freed by another rename.
+    pub fn sequential(pairs: Vec<(String, String)>) -> Self {
+        Self(
+            pairs
+                .into_iter()
+                .map(|(input, output)| Operation::Rename {
+                    source: input.clone(),
+                    input,
+                    output,
+                })
+                .collect(),
+        )
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        self.0.as_slice()
+    }
+}
+
+impl From<Vec<Operation>> for Plan {
+    fn from(operations: Vec<Operation>) -> Self {
+        Self(operations)
+    }
+}
+
+impl IntoIterator for Plan {
+    type Item = Operation;
+    type IntoIter = std::vec::IntoIter<Operation>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+/// Path of the file at `path` once `renames` of `(input, output)` paths are
+/// performed in order, where every input is a path from before the renames.
+/// A rename applies to the file itself or to the directory containing it, as
+/// long as an earlier rename has not moved the file out of that directory.
+pub fn forward<P: AsRef<Path>>(renames: &[(P, P)], path: &Path) -> PathBuf {
+    let mut current = path.to_path_buf();
+    for (input, output) in renames {
+        let (input, output) = (input.as_ref(), output.as_ref());
+        if !path.starts_with(input) {
+            continue;
+        }
+        if let Ok(rest) = current.strip_prefix(input) {
+            current = if rest.as_os_str().is_empty() {
+                output.to_path_buf()
+            } else {
+                output.join(rest)
+            };
+        }
+    }
+    current
+}
+
+fn temporary_name(input: &str, reserved: &mut HashSet<String>) -> String {
+    let path = Path::new(input);
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let mut counter = 0;
+    loop {
+        let temporary = path
+            .with_file_name(format!(".{name}.nomino-{}-{counter}", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        // A dangling symlink does not exist according to `exists`, but it
+        // still occupies the path.
+        if Path::new(temporary.as_str()).symlink_metadata().is_err()
+            && reserved.insert(temporary.clone())
+        {
+            return temporary;
+        }
+        counter += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pairs;
+
+    fn rename(input: &str, source: &str, output: &str) -> Operation {
+        Operation::Rename {
+            input: input.to_string(),
+            source: source.to_string(),
+            output: output.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_independent() {
+        let plan = Plan::new(pairs(&[("a", "x"), ("b", "y"), ("c", "c")]));
+        assert_eq!(
+            plan.operations(),
+            [
+                rename("a", "a", "x"),
+                rename("b", "b", "y"),
+                rename("c", "c", "c")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_chain() {
+        let plan = Plan::new(pairs(&[("1", "2"), ("2", "3"), ("3", "4")]));
+        assert_eq!(
+            plan.operations(),
+            [
+                rename("3", "3", "4"),
+                rename("2", "2", "3"),
+                rename("1", "1", "2")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_cycle() {
+        let plan = Plan::new(pairs(&[("a", "b"), ("b", "c"), ("c", "a"), ("d", "a")]));
+        let ops = plan.operations();
+        let Operation::Stash { input, temporary } = &ops[0] else {
+            panic!("cycle must start with a stash");
+        };
+        assert_eq!(input, "a");
+        assert_eq!(
+            &ops[1..],
+            [
+                rename("c", "c", "a"),
+                rename("b", "b", "c"),
+                rename("a", temporary, "b"),
+                rename("d", "d", "a"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_forward() {
+        let nested = pairs(&[("d1/a", "d1/b"), ("d1", "d2")]);
+        assert_eq!(forward(&nested, Path::new("d1/a/f")), Path::new("d2/b/f"));
+        assert_eq!(forward(&nested, Path::new("d1/c")), Path::new("d2/c"));
+        assert_eq!(forward(&nested, Path::new("d10")), Path::new("d10"));
+        let moved = pairs(&[("d1/a", "a"), ("d1", "d2")]);
+        assert_eq!(forward(&moved, Path::new("d1/a")), Path::new("a"));
+        let swapped = pairs(&[("a", "b"), ("b", "a")]);
+        assert_eq!(forward(&swapped, Path::new("a")), Path::new("b"));
+        assert_eq!(forward(&swapped, Path::new("b/f")), Path::new("a/f"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temporary_name_dangling_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("a");
+        let occupied = dir
+            .path()
+            .join(format!(".a.nomino-{}-0", std::process::id()));
+        std::os::unix::fs::symlink("missing", occupied.as_path()).unwrap();
+        let temporary = temporary_name(input.to_str().unwrap(), &mut HashSet::new());
+        assert_eq!(
+            temporary,
+            dir.path()
+                .join(format!(".a.nomino-{}-1", std::process::id()))
+                .to_string_lossy()
+        );
+    }
+}
@@ -0,0 +1,313 @@
+use crate::rename::Stamp;
+use anyhow::Result;
+use globset::Glob;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Component, Path};
+use walkdir::WalkDir;
+
+/// Replacement of a reference in a text file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Edit {
+    /// Byte offset of the reference in the file before it is rewritten.
+    pub offset: usize,
+    pub old: String,
+    pub new: String,
+    /// Line of the reference, only used to show it.
+    #[serde(skip)]
+    pub line: usize,
+}
+
+/// References rewritten in a text file, recorded so that they can be reverted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rewrite {
+    /// Path of the file after the run.
+    pub file: String,
+    pub edits: Vec<Edit>,
+    /// Stamp of the file once rewritten.
+    pub stamp: Option<Stamp>,
+}
+
+impl Rewrite {
+    /// Applies the edits to `contents`, or reverts them if `revert` is set.
+    /// Returns `None` if a reference is not found where it is expected.
+    pub fn apply(&self, contents: &str, revert: bool) -> Option<String> {
+        let mut applied = String::with_capacity(contents.len());
+        let mut position = 0;
+        let mut delta = 0_isize;
+        for edit in self.edits.iter() {
+            let (from, to) = if revert {
+                (edit.new.as_str(), edit.old.as_str())
+            } else {
+                (edit.old.as_str(), edit.new.as_str())
+            };
+            let offset = if revert {
+                edit.offset.checked_add_signed(delta)?
+            } else {
+                edit.offset
+            };
+            if offset < position || !contents.get(offset..)?.starts_with(from) {
+                return None;
+            }
+            applied.push_str(contents.get(position..offset)?);
+            applied.push_str(to);
+            position = offset + from.len();
+            delta += edit.new.len() as isize - edit.old.len() as isize;
+        }
+        applied.push_str(contents.get(position..)?);
+        Some(applied)
+    }
+
+    /// Rewrites `file`, i.e. the current path of the file, or reverts it if
+    /// `revert` is set, and refreshes its stamp. Fails with
+    /// `ErrorKind::InvalidData` if the file has been changed so that the
+    /// references are not found anymore.
+    pub fn write(&mut self, file: &Path, revert: bool) -> io::Result<()> {
+        let contents = fs::read_to_string(file)?;
+        let applied = self.apply(contents.as_str(), revert).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("references in '{}' have been changed", file.display()),
+            )
+        })?;
+        fs::write(file, applied)?;
+        self.stamp = Stamp::of(file);
+        Ok(())
+    }
+}
+
+/// Finds the references to the inputs of `pairs` of `(input, output)` in the
+/// text files under the working directory matched by `glob`, and the outputs
+/// they must be rewritten to. A reference is either the path of an input
+/// relative to the directory of the text file, or its filename if it is kept
+/// in the same directory. If `renamed` is set, the files have already been
+/// renamed, otherwise, text files are found at their input path.
+pub fn find_rewrites(
+    glob: &str,
+    pairs: &[(String, String)],
+    renamed: bool,
+) -> Result<Vec<Rewrite>> {
+    let matcher = Glob::new(glob)?.compile_matcher();
+    let forward: HashMap<&str, &str> = pairs
+        .iter()
+        .map(|(input, output)| (input.as_str(), output.as_str()))
+        .collect();
+    let backward: HashMap<&str, &str> = pairs
+        .iter()
+        .map(|(input, output)| (output.as_str(), input.as_str()))
+        .collect();
+    let mut rewrites = Vec::new();
+    // Git directories are skipped, so that their files are never rewritten.
+    for entry in WalkDir::new(".")
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|entry| entry.file_name() != ".git")
+        .flatten()
+    {
+        let path = entry.path();
+        let path = path.strip_prefix(".").unwrap_or(path);
+        if !entry.file_type().is_file() || !matcher.is_match(path) {
+            continue;
+        }
+        let path = path.to_string_lossy().to_string();
+        // Binary files are skipped.
+        let Ok(contents) = fs::read_to_string(path.as_str()) else {
+            continue;
+        };
+        let (original, current) = if renamed {
+            let original = backward.get(path.as_str()).copied().unwrap_or(&path);
+            (original.to_string(), path)
+        } else {
+            let current = forward.get(path.as_str()).copied().unwrap_or(&path);
+            (path.clone(), current.to_string())
+        };
+        let references = references(pairs, original.as_str(), current.as_str());
+        let edits = find_edits(contents.as_str(), references);
+        if !edits.is_empty() {
+            rewrites.push(Rewrite {
+                file: current,
+                edits,
+                stamp: None,
+            });
+        }
+    }
+    Ok(rewrites)
+}
+
+/// Pairs of `(old, new)` references to the files of `pairs` from a text file
+/// which is moved from `original` to `current`.
+fn references(pairs: &[(String, String)], original: &str, current: &str) -> Vec<(String, String)> {
+    let original_dir = Path::new(original).parent().unwrap_or(Path::new(""));
+    let current_dir = Path::new(current).parent().unwrap_or(Path::new(""));
+    let mut references = Vec::new();
+    for (input, output) in pairs {
+        if let Some(reference) = relative_to(Path::new(input), original_dir)
+            .zip(relative_to(Path::new(output), current_dir))
+        {
+            references.push(reference);
+        }
+        let (input, output) = (Path::new(input), Path::new(output));
+        if input.parent() == output.parent()
+            && let Some(reference) = input.file_name().zip(output.file_name()).map(|(old, new)| {
+                (
+                    old.to_string_lossy().to_string(),
+                    new.to_string_lossy().to_string(),
+                )
+            })
+        {
+            references.push(reference);
+        }
+    }
+    references.retain(|(old, new)| !old.is_empty() && old != new);
+    // Longer references take precedence over the filenames they end with.
+    references.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+    references.dedup_by(|a, b| a.0 == b.0);
+    references
+}
+
+/// Finds the occurrences of the references in `contents` which are not part
+/// of a longer name or path.
+fn find_edits(contents: &str, references: Vec<(String, String)>) -> Vec<Edit> {
+    if references.is_empty() {
+        return Vec::new();
+    }
+    let pattern = references
+        .iter()
+        .map(|(old, _)| regex::escape(old))
+        .collect::<Vec<_>>()
+        .join("|");
+    let Ok(re) = Regex::new(pattern.as_str()) else {
+        return Vec::new();
+    };
+    let news: HashMap<&str, &str> = references
+        .iter()
+        .map(|(old, new)| (old.as_str(), new.as_str()))
+        .collect();
+    let mut edits = Vec::new();
+    let mut start = 0;
+    while let Some(found) = re.find_at(contents, start) {
+        if !is_boundary(contents, found.start(), found.end()) {
+            start = found.start()
+                + contents[found.start()..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8);
+            continue;
+        }
+        edits.push(Edit {
+            offset: found.start(),
+            old: found.as_str().to_string(),
+            new: news[found.as_str()].to_string(),
+            line: contents[..found.start()].matches('\n').count() + 1,
+        });
+        start = found.end();
+    }
+    edits
+}
+
+fn is_name_char(ch: char) -> bool {
+    ch.is_alphanumeric() || matches!(ch, '_' | '-' | '.' | '/' | '\\')
+}
+
+/// Checks whether `contents[start..end]` is a whole reference, which may only
+/// be preceded by `./` and followed by a period ending a sentence.
+fn is_boundary(contents: &str, start: usize, end: usize) -> bool {
+    let before = &contents[..start];
+    let before = before.strip_suffix("./").unwrap_or(before);
+    let mut after = contents[end..].chars();
+    let after = match after.next() {
+        Some('.') => after.next().is_none_or(|ch| !is_name_char(ch)),
+        Some(ch) => !is_name_char(ch),
+        None => true,
+    };
+    after
+        && before
+            .chars()
+            .next_back()
+            .is_none_or(|ch| !is_name_char(ch))
+}
+
+/// Path of `path` relative to `dir`, both relative to the working directory,
+/// with '/' as separator.
+fn relative_to(path: &Path, dir: &Path) -> Option<String> {
+    let normalize = |path: &Path| -> Vec<String> {
+        let mut components: Vec<String> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir
+                    if components.last().is_some_and(|last| last.as_str() != "..") =>
+                {
+                    components.pop();
+                }
+                component => components.push(component.as_os_str().to_string_lossy().to_string()),
+            }
+        }
+        components
+    };
+    let (path, dir) = (normalize(path), normalize(dir));
+    let common = path
+        .iter()
+        .zip(dir.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if dir[common..].iter().any(|component| component == "..") {
+        return None;
+    }
+    let relative: Vec<&str> = (common..dir.len())
+        .map(|_| "..")
+        .chain(path[common..].iter().map(String::as_str))
+        .collect();
+    Some(relative.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::pairs;
+
+    #[test]
+    fn test_references() {
+        let pairs = pairs(&[("img/a.png", "img/b.png"), ("c.md", "docs/c.md")]);
+        assert_eq!(
+            references(pairs.as_slice(), "docs/index.md", "docs/index.md"),
+            [
+                ("../img/a.png".to_string(), "../img/b.png".to_string()),
+                ("../c.md".to_string(), "c.md".to_string()),
+                ("a.png".to_string(), "b.png".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_find_edits() {
+        let contents =
+            "![a](img/a.png) ![b](./img/a.png)\nsee img/a.png. not img/a.png.bak or x/img/a.png";
+        let edits = find_edits(
+            contents,
+            vec![("img/a.png".to_string(), "img/b.png".to_string())],
+        );
+        assert_eq!(
+            edits
+                .iter()
+                .map(|edit| (edit.offset, edit.line))
+                .collect::<Vec<_>>(),
+            [(5, 1), (23, 1), (38, 2)]
+        );
+        let rewrite = Rewrite {
+            file: String::new(),
+            edits,
+            stamp: None,
+        };
+        let rewritten = rewrite.apply(contents, false).unwrap();
+        assert_eq!(
+            rewritten,
+            "![a](img/b.png) ![b](./img/b.png)\nsee img/b.png. not img/a.png.bak or x/img/a.png"
+        );
+        assert_eq!(rewrite.apply(rewritten.as_str(), true).unwrap(), contents);
+        assert_eq!(rewrite.apply(contents, true), None);
+    }
+}
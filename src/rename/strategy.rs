@@ -0,0 +1,185 @@
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+use std::fs::Metadata;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConflictStrategy {
+    Skip,
+    Fail,
+    Prefix,
+    SuffixCounter,
+    Backup,
+    KeepNewer,
+    KeepLarger,
+    Overwrite,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Resolution {
+    /// The target is free.
+    Proceed,
+    /// The target is occupied, and the file is moved to this free path instead.
+    Rename(PathBuf),
+    /// The occupied target is moved to this backup path before the file is moved.
+    Backup(PathBuf),
+    /// The occupied target is replaced by the file.
+    Overwrite,
+    /// The occupied target is kept and the file is not moved.
+    Skip,
+    /// The occupied target is kept and the batch is stopped.
+    Fail,
+}
+
+impl ConflictStrategy {
+    /// Whether the strategy may replace an existing output, which cannot be
+    /// restored afterwards.
+    pub fn is_destructive(&self) -> bool {
+        matches!(self, Self::Overwrite | Self::KeepNewer | Self::KeepLarger)
+    }
+
+    /// Resolves the conflict of moving `source` to `target`, where `exists`
+    /// tells whether a path is occupied at the moment of moving.
+    pub fn resolve(
+        &self,
+        source: &Path,
+        target: &Path,
+        exists: impl Fn(&Path) -> bool,
+    ) -> Resolution {
+        if !exists(target) {
+            return Resolution::Proceed;
+        }
+        match self {
+            Self::Skip => Resolution::Skip,
+            Self::Fail => Resolution::Fail,
+            Self::Overwrite => Resolution::Overwrite,
+            Self::Prefix => {
+                let mut path = target.to_path_buf();
+                while exists(path.as_path()) {
+                    let mut name = OsString::from("_");
+                    name.push(path.file_name().unwrap_or_default());
+                    path.set_file_name(name);
+                }
+                Resolution::Rename(path)
+            }
+            Self::SuffixCounter => {
+                let stem = target.file_stem().unwrap_or_default();
+                let mut counter = 2;
+                loop {
+                    let mut name = stem.to_os_string();
+                    name.push(format!(" ({counter})"));
+                    if let Some(extension) = target.extension() {
+                        name.push(".");
+                        name.push(extension);
+                    }
+                    let path = target.with_file_name(name);
+                    if !exists(path.as_path()) {
+                        return Resolution::Rename(path);
+                    }
+                    counter += 1;
+                }
+            }
+            Self::Backup => {
+                let mut backup = target.as_os_str().to_os_string();
+                loop {
+                    backup.push("~");
+                    if !exists(Path::new(backup.as_os_str())) {
+                        return Resolution::Backup(PathBuf::from(backup));
+                    }
+                }
+            }
+            Self::KeepNewer => keep_if(source, target, |source, target| {
+                target.modified().ok() > source.modified().ok()
+            }),
+            Self::KeepLarger => {
+                keep_if(source, target, |source, target| target.len() > source.len())
+            }
+        }
+    }
+}
+
+/// Skips the file if `keep` holds for the metadata of the occupied target,
+/// otherwise the target is overwritten. Symlinks are compared themselves
+/// rather than the files they point to, as they are what is moved or replaced.
+fn keep_if(
+    source: &Path,
+    target: &Path,
+    keep: impl Fn(&Metadata, &Metadata) -> bool,
+) -> Resolution {
+    match (source.symlink_metadata(), target.symlink_metadata()) {
+        (Ok(source), Ok(target)) if keep(&source, &target) => Resolution::Skip,
+        _ => Resolution::Overwrite,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn resolve(strategy: ConflictStrategy, target: &str, occupied: &[&str]) -> Resolution {
+        strategy.resolve(Path::new("source"), Path::new(target), |path| {
+            occupied.iter().any(|occupied| Path::new(occupied) == path)
+        })
+    }
+
+    #[test]
+    fn test_free_target() {
+        assert_eq!(
+            resolve(ConflictStrategy::Fail, "a.txt", &["b.txt"]),
+            Resolution::Proceed
+        );
+    }
+
+    #[test]
+    fn test_prefix() {
+        assert_eq!(
+            resolve(
+                ConflictStrategy::Prefix,
+                "dir/a.txt",
+                &["dir/a.txt", "dir/_a.txt"]
+            ),
+            Resolution::Rename(PathBuf::from("dir/__a.txt"))
+        );
+    }
+
+    #[test]
+    fn test_suffix_counter() {
+        assert_eq!(
+            resolve(
+                ConflictStrategy::SuffixCounter,
+                "a.txt",
+                &["a.txt", "a (2).txt"]
+            ),
+            Resolution::Rename(PathBuf::from("a (3).txt"))
+        );
+        assert_eq!(
+            resolve(ConflictStrategy::SuffixCounter, "a", &["a"]),
+            Resolution::Rename(PathBuf::from("a (2)"))
+        );
+    }
+
+    #[test]
+    fn test_backup() {
+        assert_eq!(
+            resolve(ConflictStrategy::Backup, "a.txt", &["a.txt", "a.txt~"]),
+            Resolution::Backup(PathBuf::from("a.txt~~"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keep_larger_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("source");
+        let target = dir.path().join("target");
+        fs::write(source.as_path(), "0123456789").unwrap();
+        fs::write(dir.path().join("huge"), "0".repeat(1000)).unwrap();
+        std::os::unix::fs::symlink("huge", target.as_path()).unwrap();
+        assert_eq!(
+            ConflictStrategy::KeepLarger.resolve(source.as_path(), target.as_path(), |_| true),
+            Resolution::Overwrite
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::rename::{create_symlink, forward, normalize, symlink_target, temporary_path};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// Symlink found before renaming files.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Symlink {
+    /// Absolute path of the symlink.
+    pub path: PathBuf,
+    /// Target of the symlink as it is stored.
+    pub target: PathBuf,
+}
+
+/// Change of the target of a symlink, recorded so that it can be reverted.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Retarget {
+    /// Path of the symlink after the run.
+    pub link: String,
+    pub old: String,
+    pub new: String,
+}
+
+impl Retarget {
+    /// Points the symlink to its new target, or to its old one if `revert` is
+    /// set. The symlink is replaced atomically. Fails with
+    /// `ErrorKind::InvalidData` if its target has been changed since.
+    pub fn write(&self, revert: bool) -> io::Result<()> {
+        let (from, to) = if revert {
+            (self.new.as_str(), self.old.as_str())
+        } else {
+            (self.old.as_str(), self.new.as_str())
+        };
+        let link = Path::new(self.link.as_str());
+        if fs::read_link(link)? != Path::new(from) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("target of '{}' has been changed", self.link),
+            ));
+        }
+        let is_dir = link.parent().unwrap_or(Path::new("")).join(to).is_dir();
+        let temporary = temporary_path(link);
+        create_symlink(Path::new(to), is_dir, temporary.as_path())?;
+        fs::rename(temporary.as_path(), link).inspect_err(|_| {
+            let _ = fs::remove_file(temporary.as_path());
+        })
+    }
+}
+
+/// Finds the symlinks under the working directory and `roots`, without
+/// following them.
+pub fn find_symlinks(roots: &[PathBuf]) -> io::Result<Vec<Symlink>> {
+    let cwd = env::current_dir()?;
+    let mut found = HashSet::new();
+    let mut symlinks = Vec::new();
+    for root in std::iter::once(Path::new(".")).chain(roots.iter().map(PathBuf::as_path)) {
+        for entry in WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.file_name() != ".git")
+            .flatten()
+        {
+            if !entry.path_is_symlink() {
+                continue;
+            }
+            let path = normalize(cwd.join(entry.path()).as_path());
+            if !found.insert(path.clone()) {
+                continue;
+            }
+            if let Ok(target) = fs::read_link(entry.path()) {
+                symlinks.push(Symlink { path, target });
+            }
+        }
+    }
+    Ok(symlinks)
+}
+
+/// Finds the new targets of `symlinks` once `pairs` of `(input, output)` are
+/// renamed, i.e. symlinks to renamed files or to files under renamed
+/// directories, and relative symlinks which are themselves moved. Relative
+/// targets are kept relative and absolute targets are kept absolute.
+pub fn find_retargets(
+    symlinks: &[Symlink],
+    pairs: &[(String, String)],
+) -> io::Result<Vec<Retarget>> {
+    let cwd = env::current_dir()?;
+    let mut renames: Vec<(PathBuf, PathBuf)> = pairs
+        .iter()
+        .map(|(input, output)| {
+            (
+                normalize(cwd.join(input).as_path()),
+                normalize(cwd.join(output).as_path()),
+            )
+        })
+        .collect();
+    // Files are renamed before the directories containing them.
+    renames.sort_by_key(|(input, _)| Reverse(input.components().count()));
+    let mut retargets = Vec::new();
+    for symlink in symlinks {
+        let dir = symlink.path.parent().unwrap_or(Path::new(""));
+        let target = normalize(dir.join(symlink.target.as_path()).as_path());
+        let link = forward(renames.as_slice(), symlink.path.as_path());
+        let new = symlink_target(
+            forward(renames.as_slice(), target.as_path()).as_path(),
+            link.as_path(),
+            symlink.target.is_absolute(),
+        )?;
+        if new == symlink.target {
+            continue;
+        }
+        let link = link.strip_prefix(cwd.as_path()).unwrap_or(link.as_path());
+        retargets.push(Retarget {
+            link: link.to_string_lossy().to_string(),
+            old: symlink.target.to_string_lossy().to_string(),
+            new: new.to_string_lossy().to_string(),
+        });
+    }
+    Ok(retargets)
+}
@@ -0,0 +1,230 @@
+use crate::rename::{Entry, Journal, Mode, revert_transfer, transfer};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+
+/// In-memory view of the changes a batch would make, used in test mode so that
+/// the preview matches a real run.
+#[derive(Default)]
+struct Simulation {
+    created: HashSet<PathBuf>,
+    removed: HashSet<PathBuf>,
+}
+
+impl Simulation {
+    fn exists(&self, path: &Path) -> bool {
+        if self.created.contains(path) {
+            return true;
+        }
+        if path
+            .ancestors()
+            .any(|ancestor| self.removed.contains(ancestor))
+        {
+            return false;
+        }
+        path.symlink_metadata().is_ok()
+    }
+
+    fn create(&mut self, path: &Path) {
+        self.removed.remove(path);
+        self.created.insert(path.to_path_buf());
+    }
+
+    fn transfer(&mut self, mode: Mode, from: &Path, to: &Path, replace: bool) -> io::Result<()> {
+        if !replace && self.exists(to) {
+            return Err(io::Error::from(ErrorKind::AlreadyExists));
+        }
+        let parent = to.parent().filter(|parent| !parent.as_os_str().is_empty());
+        if !self.exists(from) || parent.is_some_and(|parent| !self.exists(parent)) {
+            return Err(io::Error::from(ErrorKind::NotFound));
+        }
+        if mode == Mode::Move {
+            self.created.remove(from);
+            self.removed.insert(from.to_path_buf());
+        }
+        self.create(to);
+        Ok(())
+    }
+}
+
+/// Keeps track of every change made to the filesystem, so that they can be
+/// reverted if a batch must be all-or-nothing.
+#[derive(Default)]
+pub struct Transaction {
+    renames: Vec<(PathBuf, PathBuf, Mode)>,
+    directories: Vec<PathBuf>,
+    journal: Option<Journal>,
+    simulation: Option<Simulation>,
+    mode: Mode,
+    verify: bool,
+}
+
+impl Transaction {
+    /// Simulates every change in memory without touching the filesystem.
+    pub fn simulated() -> Self {
+        Self {
+            simulation: Some(Simulation::default()),
+            ..Default::default()
+        }
+    }
+
+    /// Records every change to `journal` before and after it is made.
+    pub fn with_journal(journal: Journal) -> Self {
+        Self {
+            journal: Some(journal),
+            ..Default::default()
+        }
+    }
+
+    /// Continues an interrupted batch whose changes are read from its journal.
+    pub fn resume(
+        renames: Vec<(String, String, Mode)>,
+        directories: Vec<String>,
+        journal: Journal,
+    ) -> Self {
+        Self {
+            renames: renames
+                .into_iter()
+                .map(|(from, to, mode)| (PathBuf::from(from), PathBuf::from(to), mode))
+                .collect(),
+            directories: directories.into_iter().map(PathBuf::from).collect(),
+            journal: Some(journal),
+            ..Default::default()
+        }
+    }
+
+    /// Creates outputs with `transfer` and `transfer_noreplace` according to
+    /// `mode`, e.g. as copies of their input.
+    pub fn mode(mut self, mode: Mode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Compares the contents of files copied across filesystems with their
+    /// source before removing it.
+    pub fn verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Checks whether `path` exists, taking the changes made so far into account.
+    pub fn exists(&self, path: &Path) -> bool {
+        self.simulation.as_ref().map_or_else(
+            || path.symlink_metadata().is_ok(),
+            |simulation| simulation.exists(path),
+        )
+    }
+
+    /// Recursively creates all missing parent directories of `path`.
+    pub fn create_parent_dirs(&mut self, path: &Path) -> io::Result<()> {
+        let mut missing: Vec<&Path> = path
+            .ancestors()
+            .skip(1)
+            .take_while(|dir| !dir.as_os_str().is_empty() && !self.exists(dir))
+            .collect();
+        while let Some(dir) = missing.pop() {
+            if let Some(simulation) = self.simulation.as_mut() {
+                simulation.create(dir);
+                continue;
+            }
+            self.log(Entry::Mkdir {
+                path: dir.to_string_lossy().to_string(),
+            })?;
+            fs::create_dir(dir)?;
+            self.directories.push(dir.to_path_buf());
+        }
+        Ok(())
+    }
+
+    /// Renames `from` to `to`, replacing `to` if it exists.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.apply(Mode::Move, from, to, true)
+    }
+
+    /// Renames `from` to `to`, failing with `ErrorKind::AlreadyExists` if `to`
+    /// exists.
+    pub fn rename_noreplace(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.apply(Mode::Move, from, to, false)
+    }
+
+    /// Creates `to` from `from` according to the mode of the transaction,
+    /// replacing `to` if it exists.
+    pub fn transfer(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.apply(self.mode, from, to, true)
+    }
+
+    /// Creates `to` from `from` according to the mode of the transaction,
+    /// failing with `ErrorKind::AlreadyExists` if `to` exists.
+    pub fn transfer_noreplace(&mut self, from: &Path, to: &Path) -> io::Result<()> {
+        self.apply(self.mode, from, to, false)
+    }
+
+    pub fn journal(&self) -> Option<&Path> {
+        self.journal.as_ref().map(Journal::path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.renames.is_empty() && self.directories.is_empty()
+    }
+
+    /// Marks the batch as completed by removing its journal.
+    pub fn commit(self) -> io::Result<()> {
+        self.journal.map_or(Ok(()), Journal::remove)
+    }
+
+    /// Reverts all renames, copies and links in reverse order and removes the directories created
+    /// by this transaction. Returns the errors of the steps which could not be
+    /// reverted, in which case the journal is kept.
+    pub fn rollback(mut self) -> Vec<(PathBuf, io::Error)> {
+        let mut errors = Vec::new();
+        if self.simulation.is_some() {
+            return errors;
+        }
+        while let Some((from, to, mode)) = self.renames.pop() {
+            match revert_transfer(mode, from.as_path(), to.as_path(), self.verify) {
+                Ok(_) => {
+                    if let Err(e) = self.log(Entry::Undo) {
+                        errors.push((to, e));
+                    }
+                }
+                Err(e) => errors.push((to, e)),
+            }
+        }
+        while let Some(dir) = self.directories.pop() {
+            if let Err(e) = fs::remove_dir(dir.as_path()) {
+                errors.push((dir, e));
+            }
+        }
+        if errors.is_empty()
+            && let Some(journal) = self.journal
+        {
+            let path = journal.path().to_path_buf();
+            if let Err(e) = journal.remove() {
+                errors.push((path, e));
+            }
+        }
+        errors
+    }
+
+    fn apply(&mut self, mode: Mode, from: &Path, to: &Path, replace: bool) -> io::Result<()> {
+        if let Some(simulation) = self.simulation.as_mut() {
+            return simulation.transfer(mode, from, to, replace);
+        }
+        self.log(Entry::Begin {
+            source: from.to_string_lossy().to_string(),
+            target: to.to_string_lossy().to_string(),
+            mode,
+        })?;
+        transfer(mode, from, to, replace, self.verify)?;
+        self.renames
+            .push((from.to_path_buf(), to.to_path_buf(), mode));
+        self.log(Entry::End)
+    }
+
+    fn log(&mut self, entry: Entry) -> io::Result<()> {
+        self.journal
+            .as_mut()
+            .map_or(Ok(()), |journal| journal.write(&entry))
+    }
+}
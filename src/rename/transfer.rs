@@ -0,0 +1,316 @@
+use crate::rename::rename_noreplace;
+use filetime::FileTime;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::path::{Component, Path, PathBuf};
+
+/// How an output is created from its input.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Mode {
+    #[default]
+    Move,
+    Copy,
+    Hardlink,
+    /// Symlink whose target is relative to the directory of the link.
+    Symlink,
+    AbsoluteSymlink,
+}
+
+/// Creates `to` from `from` according to `mode`, replacing `to` only if
+/// `replace` is set.
+pub fn transfer(mode: Mode, from: &Path, to: &Path, replace: bool, verify: bool) -> io::Result<()> {
+    if mode == Mode::Move {
+        return move_path(from, to, replace, verify);
+    }
+    // Links and copies are created next to `to` first if it must be replaced,
+    // so that `to` is replaced atomically.
+    let link = if replace && mode != Mode::Copy {
+        temporary_path(to)
+    } else {
+        to.to_path_buf()
+    };
+    match mode {
+        Mode::Copy => return copy_path(from, to, replace, verify),
+        Mode::Hardlink => fs::hard_link(from, link.as_path())?,
+        Mode::Symlink | Mode::AbsoluteSymlink => {
+            let target = symlink_target(from, to, mode == Mode::AbsoluteSymlink)?;
+            create_symlink(target.as_path(), from.is_dir(), link.as_path())?
+        }
+        Mode::Move => unreachable!(),
+    }
+    if replace {
+        fs::rename(link.as_path(), to).inspect_err(|_| {
+            let _ = fs::remove_file(link.as_path());
+        })?;
+    }
+    Ok(())
+}
+
+/// Reverts a `transfer` of `from` to `to`.
+pub fn revert_transfer(mode: Mode, from: &Path, to: &Path, verify: bool) -> io::Result<()> {
+    match mode {
+        Mode::Move => move_path(to, from, false, verify),
+        _ => remove_tree(to),
+    }
+}
+
+/// Moves `from` to `to`, replacing `to` only if `replace` is set. If both
+/// paths are on different filesystems, `from` is copied along with its
+/// permissions, timestamps and extended attributes, then removed once the copy
+/// is verified. The contents of copied files are compared with their source
+/// only if `verify` is set, otherwise, only their sizes are compared.
+pub fn move_path(from: &Path, to: &Path, replace: bool, verify: bool) -> io::Result<()> {
+    let result = if replace {
+        fs::rename(from, to)
+    } else {
+        rename_noreplace(from, to)
+    };
+    match result {
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            copy_and_remove(from, to, replace, verify)
+        }
+        result => result,
+    }
+}
+
+fn copy_and_remove(from: &Path, to: &Path, replace: bool, verify: bool) -> io::Result<()> {
+    copy_path(from, to, replace, verify)?;
+    // `from` is moved aside before it is removed, so that a failure leaves
+    // either `from` intact without its copy, or only the complete copy at `to`.
+    let removed = temporary_path(from);
+    if let Err(e) = fs::rename(from, removed.as_path()) {
+        let _ = remove_tree(to);
+        return Err(e);
+    }
+    // The move is complete at this point, thus leftovers are not an error.
+    let _ = remove_tree(removed.as_path());
+    Ok(())
+}
+
+fn copy_path(from: &Path, to: &Path, replace: bool, verify: bool) -> io::Result<()> {
+    // The copy is made next to `to` first, so that `to` never appears partially.
+    let temporary = temporary_path(to);
+    let result = copy_tree(from, temporary.as_path(), verify).and_then(|_| {
+        if replace {
+            fs::rename(temporary.as_path(), to)
+        } else {
+            rename_noreplace(temporary.as_path(), to)
+        }
+    });
+    if result.is_err() {
+        let _ = remove_tree(temporary.as_path());
+    }
+    result
+}
+
+pub(crate) fn temporary_path(to: &Path) -> PathBuf {
+    let name = to
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+    let mut counter = 0;
+    loop {
+        let path = to.with_file_name(format!(
+            ".{name}.nomino-copy-{}-{counter}",
+            std::process::id()
+        ));
+        if path.symlink_metadata().is_err() {
+            return path;
+        }
+        counter += 1;
+    }
+}
+
+fn copy_tree(from: &Path, to: &Path, verify: bool) -> io::Result<()> {
+    let metadata = from.symlink_metadata()?;
+    if metadata.file_type().is_symlink() {
+        copy_symlink(from, to)?;
+    } else if metadata.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_tree(
+                entry.path().as_path(),
+                to.join(entry.file_name()).as_path(),
+                verify,
+            )?;
+        }
+    } else {
+        fs::copy(from, to)?;
+        verify_copy(from, to, &metadata, verify)?;
+    }
+    copy_metadata(from, to, &metadata)
+}
+
+fn copy_symlink(from: &Path, to: &Path) -> io::Result<()> {
+    create_symlink(fs::read_link(from)?.as_path(), from.is_dir(), to)
+}
+
+#[cfg(unix)]
+pub(crate) fn create_symlink(target: &Path, _is_dir: bool, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+pub(crate) fn create_symlink(target: &Path, is_dir: bool, link: &Path) -> io::Result<()> {
+    if is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+/// Target of a symlink at `link` which points to `path`, either absolute or
+/// relative to the directory of `link`.
+pub(crate) fn symlink_target(path: &Path, link: &Path, absolute: bool) -> io::Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    let path = normalize(cwd.join(path).as_path());
+    if absolute {
+        return Ok(path);
+    }
+    let link = normalize(cwd.join(link).as_path());
+    let base: Vec<Component> = link
+        .parent()
+        .map(|parent| parent.components().collect())
+        .unwrap_or_default();
+    let components: Vec<Component> = path.components().collect();
+    let common = base
+        .iter()
+        .zip(components.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut target = PathBuf::new();
+    (common..base.len()).for_each(|_| target.push(Component::ParentDir));
+    components[common..]
+        .iter()
+        .for_each(|component| target.push(component));
+    Ok(target)
+}
+
+/// Lexically removes `.` and `..` components of an absolute `path`.
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+fn verify_copy(from: &Path, to: &Path, metadata: &Metadata, verify: bool) -> io::Result<()> {
+    let mismatch = || {
+        io::Error::other(format!(
+            "copy of '{}' does not match its source",
+            from.display()
+        ))
+    };
+    if fs::metadata(to)?.len() != metadata.len() {
+        return Err(mismatch());
+    }
+    if !verify {
+        return Ok(());
+    }
+    let mut source = BufReader::new(File::open(from)?);
+    let mut copy = BufReader::new(File::open(to)?);
+    let (mut source_buf, mut copy_buf) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let len = source.read(source_buf.as_mut_slice())?;
+        if len == 0 {
+            return Ok(());
+        }
+        copy.read_exact(&mut copy_buf[..len])?;
+        if source_buf[..len] != copy_buf[..len] {
+            return Err(mismatch());
+        }
+    }
+}
+
+fn copy_metadata(from: &Path, to: &Path, metadata: &Metadata) -> io::Result<()> {
+    // Extended attributes are not supported by every filesystem, and some of
+    // them, e.g. of security modules, cannot be set by users, which must not
+    // prevent moving the file.
+    #[cfg(unix)]
+    for name in xattr::list(from).into_iter().flatten() {
+        if let Ok(Some(value)) = xattr::get(from, name.as_os_str()) {
+            let _ = xattr::set(to, name.as_os_str(), value.as_slice());
+        }
+    }
+    let is_symlink = metadata.file_type().is_symlink();
+    if !is_symlink {
+        fs::set_permissions(to, metadata.permissions())?;
+    }
+    let accessed = FileTime::from_last_access_time(metadata);
+    let modified = FileTime::from_last_modification_time(metadata);
+    if is_symlink {
+        filetime::set_symlink_file_times(to, accessed, modified)
+    } else {
+        filetime::set_file_times(to, accessed, modified)
+    }
+}
+
+fn remove_tree(path: &Path) -> io::Result<()> {
+    if path.symlink_metadata()?.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_symlink_target() {
+        let target = symlink_target(Path::new("a/b/file"), Path::new("a/c/./d/link"), false);
+        assert_eq!(target.unwrap(), Path::new("../../b/file"));
+        let target = symlink_target(Path::new("file"), Path::new("link"), false);
+        assert_eq!(target.unwrap(), Path::new("file"));
+    }
+
+    #[test]
+    fn test_copy_and_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        let to = dir.path().join("to");
+        fs::create_dir(from.as_path()).unwrap();
+        fs::write(from.join("file"), "contents").unwrap();
+        let mtime = FileTime::from_unix_time(1_000_000_000, 0);
+        filetime::set_file_mtime(from.join("file"), mtime).unwrap();
+
+        copy_and_remove(from.as_path(), to.as_path(), false, true).unwrap();
+
+        assert!(!from.exists());
+        assert_eq!(fs::read_to_string(to.join("file")).unwrap(), "contents");
+        assert_eq!(
+            FileTime::from_last_modification_time(&fs::metadata(to.join("file")).unwrap()),
+            mtime
+        );
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copy_and_remove_failure() {
+        let dir = tempfile::tempdir().unwrap();
+        let from = dir.path().join("from");
+        let to = dir.path().join("to");
+        fs::create_dir(from.as_path()).unwrap();
+        fs::write(from.join("file"), "contents").unwrap();
+        // Sockets cannot be copied, which makes the copy fail partway.
+        let _socket = std::os::unix::net::UnixListener::bind(from.join("socket")).unwrap();
+
+        assert!(copy_and_remove(from.as_path(), to.as_path(), false, true).is_err());
+
+        assert_eq!(fs::read_to_string(from.join("file")).unwrap(), "contents");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
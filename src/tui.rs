@@ -0,0 +1,464 @@
+use crate::errors::FormatError;
+use crate::input::{Formatter, Source, match_entries, walk_entries};
+use crate::rename::{Mode, check_conflicts};
+use anyhow::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+
+const GROUP_COLORS: [Color; 5] = [
+    Color::Cyan,
+    Color::Magenta,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Focus {
+    Regex,
+    Output,
+}
+
+/// Single line text input whose cursor is counted in characters.
+#[derive(Debug, Default, PartialEq)]
+struct Field {
+    text: String,
+    cursor: usize,
+}
+
+impl Field {
+    fn new(text: String) -> Self {
+        let cursor = text.chars().count();
+        Self { text, cursor }
+    }
+
+    fn byte_index(&self) -> usize {
+        self.text
+            .char_indices()
+            .nth(self.cursor)
+            .map_or(self.text.len(), |(i, _)| i)
+    }
+
+    fn insert(&mut self, ch: char) {
+        let i = self.byte_index();
+        self.text.insert(i, ch);
+        self.cursor += 1;
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            let i = self.byte_index();
+            self.text.remove(i);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.text.chars().count() {
+            let i = self.byte_index();
+            self.text.remove(i);
+        }
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.text.chars().count());
+    }
+}
+
+struct PreviewRow {
+    input: String,
+    /// Byte ranges of the matched groups in `input`, by group index.
+    groups: Vec<Option<Range<usize>>>,
+    output: String,
+    collision: Option<&'static str>,
+}
+
+enum Preview {
+    Rows(Vec<PreviewRow>),
+    Error {
+        focus: Focus,
+        message: String,
+        position: Option<usize>,
+    },
+}
+
+/// Entries of the working directory, which are only walked again when the
+/// depths to walk change rather than on every keystroke.
+#[derive(Default)]
+struct Entries {
+    depths: Option<(usize, usize)>,
+    entries: Vec<(usize, String)>,
+}
+
+impl Entries {
+    fn get(&mut self, depth: usize, max_depth: usize) -> &[(usize, String)] {
+        if self.depths != Some((depth, max_depth)) {
+            self.entries = walk_entries(depth, max_depth);
+            self.depths = Some((depth, max_depth));
+        }
+        self.entries.as_slice()
+    }
+}
+
+struct App {
+    regex: Field,
+    output: Field,
+    focus: Focus,
+    depth: Option<usize>,
+    max_depth: Option<usize>,
+    preserve_extension: bool,
+    entries: Entries,
+    preview: Preview,
+    table: TableState,
+}
+
+/// Runs the terminal UI with the initial `regex` and `output` patterns, and
+/// returns the patterns to rename files with, or `None` if the user quits.
+pub fn run(
+    regex: String,
+    output: String,
+    depth: Option<usize>,
+    max_depth: Option<usize>,
+    preserve_extension: bool,
+) -> Result<Option<(String, String)>> {
+    let mut app = App {
+        regex: Field::new(regex),
+        output: Field::new(output),
+        focus: Focus::Regex,
+        depth,
+        max_depth,
+        preserve_extension,
+        entries: Entries::default(),
+        preview: Preview::Rows(Vec::new()),
+        table: TableState::default(),
+    };
+    app.refresh();
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<Option<(String, String)>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let field = match self.focus {
+                Focus::Regex => &mut self.regex,
+                Focus::Output => &mut self.output,
+            };
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(None);
+                }
+                KeyCode::Enter => {
+                    if matches!(&self.preview, Preview::Rows(rows) if !rows.is_empty()) {
+                        return Ok(Some((self.regex.text.clone(), self.output.text.clone())));
+                    }
+                }
+                KeyCode::Tab | KeyCode::BackTab => {
+                    self.focus = match self.focus {
+                        Focus::Regex => Focus::Output,
+                        Focus::Output => Focus::Regex,
+                    };
+                }
+                KeyCode::Up => self.table.select_previous(),
+                KeyCode::Down => self.table.select_next(),
+                KeyCode::Left => field.move_to(field.cursor.saturating_sub(1)),
+                KeyCode::Right => field.move_to(field.cursor + 1),
+                KeyCode::Home => field.move_to(0),
+                KeyCode::End => field.move_to(usize::MAX),
+                KeyCode::Backspace => {
+                    field.backspace();
+                    self.refresh();
+                }
+                KeyCode::Delete => {
+                    field.delete();
+                    self.refresh();
+                }
+                KeyCode::Char(ch) => {
+                    field.insert(ch);
+                    self.refresh();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn refresh(&mut self) {
+        self.preview = preview(
+            &mut self.entries,
+            self.regex.text.as_str(),
+            self.output.text.as_str(),
+            self.depth,
+            self.max_depth,
+            self.preserve_extension,
+        );
+        self.table.select(None);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [regex_area, output_area, preview_area, help_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.draw_field(frame, Focus::Regex, regex_area);
+        self.draw_field(frame, Focus::Output, output_area);
+        match &self.preview {
+            Preview::Rows(rows) => {
+                let table = Table::new(
+                    rows.iter().map(|row| {
+                        Row::new([
+                            Cell::from(highlight_groups(row)),
+                            Cell::from(row.output.as_str()),
+                            Cell::from(row.collision.unwrap_or_default()).red(),
+                        ])
+                        .style(if row.collision.is_some() {
+                            Style::new().red()
+                        } else {
+                            Style::new()
+                        })
+                    }),
+                    [
+                        Constraint::Percentage(45),
+                        Constraint::Percentage(45),
+                        Constraint::Percentage(10),
+                    ],
+                )
+                .header(Row::new(["Input", "Output", "Status"]).cyan().bold())
+                .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+                .block(Block::bordered().title(format!(" {} files ", rows.len())));
+                frame.render_stateful_widget(table, preview_area, &mut self.table);
+            }
+            Preview::Error { message, .. } => frame.render_widget(
+                Paragraph::new(message.as_str())
+                    .red()
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(" Error ")),
+                preview_area,
+            ),
+        }
+        frame.render_widget(
+            Line::from(" Tab: switch field  Up/Down: scroll  Enter: rename files  Esc: quit")
+                .dark_gray(),
+            help_area,
+        );
+    }
+
+    fn draw_field(&self, frame: &mut Frame, focus: Focus, area: Rect) {
+        let (field, title) = match focus {
+            Focus::Regex => (&self.regex, " Regex "),
+            Focus::Output => (&self.output, " Output "),
+        };
+        let error = match &self.preview {
+            Preview::Error {
+                focus: error_focus,
+                position,
+                ..
+            } if *error_focus == focus => *position,
+            _ => None,
+        };
+        let line: Line = field
+            .text
+            .chars()
+            .enumerate()
+            .map(|(i, ch)| {
+                let span = Span::raw(ch.to_string());
+                if Some(i) == error {
+                    span.on_red()
+                } else {
+                    span
+                }
+            })
+            .collect();
+        let block = if self.focus == focus {
+            Block::bordered().title(title).yellow()
+        } else {
+            Block::bordered().title(title)
+        };
+        frame.render_widget(Paragraph::new(line).white().block(block), area);
+        if self.focus == focus {
+            frame.set_cursor_position((
+                (area.x + 1).saturating_add(u16::try_from(field.cursor).unwrap_or(u16::MAX)),
+                area.y + 1,
+            ));
+        }
+    }
+}
+
+/// Colors the parts of the input which are matched by a group, where nested
+/// groups take precedence over their parent.
+fn highlight_groups(row: &PreviewRow) -> Line<'_> {
+    let group_at = |i: usize| {
+        row.groups
+            .iter()
+            .rposition(|group| group.as_ref().is_some_and(|group| group.contains(&i)))
+    };
+    let mut spans = Vec::new();
+    let mut start = 0;
+    let mut current = group_at(0);
+    for (i, _) in row.input.char_indices().skip(1) {
+        let group = group_at(i);
+        if group != current {
+            spans.push(group_span(&row.input[start..i], current));
+            start = i;
+            current = group;
+        }
+    }
+    spans.push(group_span(&row.input[start..], current));
+    Line::from(spans)
+}
+
+fn group_span(text: &str, group: Option<usize>) -> Span<'_> {
+    match group {
+        Some(group) => Span::raw(text)
+            .fg(GROUP_COLORS[group % GROUP_COLORS.len()])
+            .bold(),
+        None => Span::raw(text),
+    }
+}
+
+fn preview(
+    entries: &mut Entries,
+    regex: &str,
+    output: &str,
+    depth: Option<usize>,
+    max_depth: Option<usize>,
+    preserve_extension: bool,
+) -> Preview {
+    if regex.is_empty() {
+        return Preview::Rows(Vec::new());
+    }
+    let error = |focus: Focus, message: String, position: Option<usize>| Preview::Error {
+        focus,
+        message,
+        position,
+    };
+    let re = match Regex::new(regex) {
+        Ok(re) => re,
+        Err(e) => return error(Focus::Regex, e.to_string(), None),
+    };
+    if output.is_empty() {
+        return error(Focus::Output, FormatError::EmptyFormatter.to_string(), None);
+    }
+    let formatter = match Formatter::new(output) {
+        Ok(formatter) => formatter,
+        Err(e) => return error(Focus::Output, e.to_string(), e.position()),
+    };
+    let (depth, max_depth) = match Source::new_regex(regex, depth, max_depth) {
+        Ok(Source::Regex(_, depth, max_depth)) => (depth, max_depth.unwrap_or(depth)),
+        Ok(_) => unreachable!(),
+        Err(e) => return error(Focus::Regex, e.to_string(), None),
+    };
+    let pairs = match match_entries(
+        entries.get(depth, max_depth),
+        &formatter,
+        &re,
+        preserve_extension,
+    ) {
+        Ok(pairs) => pairs,
+        Err(e) => return error(Focus::Output, e.to_string(), e.position()),
+    };
+    let mut collisions = HashMap::new();
+    if let Err(e) = check_conflicts(pairs.as_slice(), false, Mode::Move) {
+        for conflict in e.conflicts() {
+            let collision = if conflict.inputs.len() > 1 {
+                "duplicate"
+            } else {
+                "exists"
+            };
+            collisions.insert(conflict.output.clone(), collision);
+        }
+    }
+    Preview::Rows(
+        pairs
+            .into_iter()
+            .map(|(input, output)| PreviewRow {
+                groups: re
+                    .captures(input.as_str())
+                    .map(|captures| {
+                        captures
+                            .iter()
+                            .skip(1)
+                            .map(|group| group.map(|group| group.range()))
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                collision: collisions.get(output.as_str()).copied(),
+                input,
+                output,
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        let mut field = Field::new(String::from("aé"));
+        field.move_to(1);
+        field.insert('b');
+        field.delete();
+        field.move_to(usize::MAX);
+        field.insert('c');
+        assert_eq!(
+            field,
+            Field {
+                text: String::from("abc"),
+                cursor: 3
+            }
+        );
+        field.backspace();
+        field.move_to(0);
+        field.backspace();
+        assert_eq!(field.text, "ab");
+    }
+
+    #[test]
+    fn test_preview_error() {
+        let Preview::Error {
+            focus, position, ..
+        } = preview(&mut Entries::default(), ".*", "init {1", None, None, true)
+        else {
+            panic!("an unclosed placeholder must be reported");
+        };
+        assert_eq!(focus, Focus::Output);
+        assert_eq!(position, Some(5));
+    }
+
+    #[test]
+    fn test_highlight_groups() {
+        let row = PreviewRow {
+            input: String::from("S1.E2"),
+            groups: vec![Some(0..2), Some(1..2), None],
+            output: String::new(),
+            collision: None,
+        };
+        let spans: Vec<String> = highlight_groups(&row)
+            .spans
+            .into_iter()
+            .map(|span| span.content.to_string())
+            .collect();
+        assert_eq!(spans, ["S", "1", ".E2"]);
+    }
+}
@@ -0,0 +1,11 @@
+use std::fs::read_dir;
+
+/// Sorted names of the entries of `dir`.
+pub fn list_files(dir: &tempfile::TempDir) -> Vec<String> {
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+    files.sort();
+    files
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::useless_vec, clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use nomino::input::MAIN_SEPARATOR;
 use std::fs::File;
@@ -6,6 +8,7 @@ use std::fs::read_dir;
 
 #[test]
 fn test_default() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -24,6 +27,7 @@ fn test_default() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -50,6 +54,7 @@ fn test_default() {
 
 #[test]
 fn test_default_not_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -68,8 +73,10 @@ fn test_default_not_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             r".*E(\d+).*",
@@ -92,8 +99,55 @@ fn test_default_not_overwrite() {
     dir.close().unwrap();
 }
 
+#[test]
+fn test_default_conflict() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut inputs = vec![
+        "Nomino (2020) S1.E1.720p.mkv",
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+        "02.mkv",
+    ];
+
+    for input in inputs.iter() {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r".*E(\d+)\..*",
+            "{:2}.mkv",
+        ])
+        .output()
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+    inputs.sort();
+
+    let stderr = String::from_utf8_lossy(cmd.stderr.as_slice());
+    assert!(!cmd.status.success());
+    assert!(stderr.contains("'01.mkv' <- 'Nomino (2020) S1.E1."));
+    assert!(stderr.contains("'02.mkv' <- 'Nomino (2020) S1.E2.1080p.mkv' (existing file)"));
+    assert_eq!(files, inputs);
+
+    dir.close().unwrap();
+}
+
 #[test]
 fn test_default_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -112,8 +166,10 @@ fn test_default_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-w",
@@ -137,8 +193,180 @@ fn test_default_overwrite() {
     dir.close().unwrap();
 }
 
+#[test]
+fn test_default_test_mode() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut inputs = vec![
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+    ];
+
+    for input in inputs.iter() {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "-t",
+            "-k",
+            "--allow-conflicts",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r".*E(\d+).*",
+            format!("new{}1.mkv", MAIN_SEPARATOR).as_str(),
+        ])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+    inputs.sort();
+
+    let stdout = String::from_utf8_lossy(cmd.stdout.as_slice());
+    assert!(cmd.status.success());
+    assert!(stdout.contains("_1.mkv"));
+    assert_eq!(files, inputs);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_on_conflict() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = vec![
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+        "Nomino (2020) S1.E3.1080p.mkv",
+        "1.mkv",
+    ];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let mut outputs = vec!["1.mkv", "1.mkv~", "1 (2).mkv", "1 (3).mkv"];
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--on-conflict",
+            "backup",
+            r".*E1.*",
+            "1.mkv",
+        ])
+        .unwrap();
+    assert!(cmd.status.success());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--allow-conflicts",
+            "--on-conflict",
+            "suffix-counter",
+            r".*E(2|3).*",
+            "1.mkv",
+        ])
+        .unwrap();
+    assert!(cmd.status.success());
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+    outputs.sort();
+
+    assert_eq!(files, outputs);
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--allow-conflicts",
+            "--on-conflict",
+            "skip",
+            r"1 \((\d)\)\.mkv",
+            "1.mkv",
+        ])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+
+    assert!(cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stdout.as_slice()).contains("skipped"));
+    assert_eq!(files, outputs);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_on_conflict_duplicates() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    for input in ["a.mkv", "b.mkv"] {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--on-conflict",
+            "suffix-counter",
+            r".*\.mkv",
+            "1.mkv",
+        ])
+        .output()
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+
+    assert!(!cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stderr.as_slice()).contains("'1.mkv'"));
+    assert_eq!(files, ["a.mkv", "b.mkv"]);
+
+    dir.close().unwrap();
+}
+
 #[test]
 fn test_default_subdir() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -162,6 +390,7 @@ fn test_default_subdir() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -198,6 +427,7 @@ fn test_default_subdir() {
 
 #[test]
 fn test_default_subdir_depth() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -221,6 +451,7 @@ fn test_default_subdir_depth() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "--depth",
@@ -259,6 +490,7 @@ fn test_default_subdir_depth() {
 
 #[test]
 fn test_default_subdir_max_depth() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -282,6 +514,7 @@ fn test_default_subdir_max_depth() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "--depth",
@@ -322,6 +555,7 @@ fn test_default_subdir_max_depth() {
 
 #[test]
 fn test_default_subdir_not_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -345,8 +579,10 @@ fn test_default_subdir_not_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-k",
@@ -381,6 +617,7 @@ fn test_default_subdir_not_overwrite() {
 
 #[test]
 fn test_default_subdir_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -404,8 +641,10 @@ fn test_default_subdir_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-k",
@@ -438,3 +677,328 @@ fn test_default_subdir_overwrite() {
 
     dir.close().unwrap();
 }
+
+#[test]
+fn test_default_mode_copy() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = [
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+    ];
+
+    for input in inputs {
+        std::fs::write(dir.path().join(input), input).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "--mode",
+            "copy",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r".*E(\d+).*",
+            "{:2}.mkv",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert_eq!(read_dir(dir.path()).unwrap().count(), 4);
+    for (input, output) in inputs.iter().zip(["01.mkv", "02.mkv"]) {
+        assert!(dir.path().join(input).exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(output)).unwrap(),
+            *input
+        );
+    }
+
+    dir.close().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_default_mode_symlink() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let input = "Nomino (2020) S1.E1.1080p.mkv";
+    let _ = File::create(dir.path().join(input)).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "-k",
+            "--mode",
+            "symlink",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r".*E(\d+).*",
+            format!("view{MAIN_SEPARATOR}{{:2}}.mkv").as_str(),
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join(input).is_file());
+    assert_eq!(
+        std::fs::read_link(dir.path().join("view").join("01.mkv")).unwrap(),
+        std::path::Path::new("..").join(input)
+    );
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_interactive() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = [
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+        "Nomino (2020) S1.E3.1080p.mkv",
+    ];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let map = dir.path().join("map.json");
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "-i",
+            "-g",
+            map.to_str().unwrap(),
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-s",
+            "asc",
+            "{:2}.mkv",
+        ])
+        .write_stdin("y\nn\ne\nthird.mkv\ny\n")
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+    files.sort();
+
+    assert!(cmd.status.success());
+    assert_eq!(
+        files,
+        [
+            "01.mkv",
+            "Nomino (2020) S1.E2.1080p.mkv",
+            "map.json",
+            "third.mkv"
+        ]
+    );
+    let map: serde_json::Value =
+        serde_json::from_str(std::fs::read_to_string(map).unwrap().as_str()).unwrap();
+    assert_eq!(map.as_object().unwrap().len(), 2);
+
+    dir.close().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_default_edit() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = ["a.txt", "b.txt", "c.txt"];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .env_remove("VISUAL")
+        .env("EDITOR", "sed -i -e s/a.txt/dir\\/x.txt/ -e s/c.txt/y.txt/")
+        .args(["--edit", "-d", dir.path().to_str().unwrap()])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("dir").join("x.txt").is_file());
+    assert!(dir.path().join("b.txt").is_file());
+    assert!(dir.path().join("y.txt").is_file());
+    assert_eq!(read_dir(dir.path()).unwrap().count(), 3);
+
+    dir.close().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_default_edit_deleted() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let _ = File::create(dir.path().join("a.txt")).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .env_remove("VISUAL")
+        .env("EDITOR", "sed -i -e /a.txt/d")
+        .args(["--edit", "-d", dir.path().to_str().unwrap()])
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert!(dir.path().join("a.txt").is_file());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_sanitize() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let input = "Nomino (2020) S1.E1.1080p.mkv";
+    let _ = File::create(dir.path().join(input)).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "--sanitize",
+            "windows",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r"(.*) \(\d+\) (.*)\.1080p.*",
+            "{1}: {2}?.mkv",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stderr.as_slice()).contains("contains ':'"));
+    assert!(dir.path().join(input).is_file());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "--sanitize",
+            "windows",
+            "--sanitize-with",
+            "",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r"(.*) \(\d+\) (.*)\.1080p.*",
+            "{1}: {2}?.mkv",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("Nomino S1.E1.mkv").is_file());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_sanitize_captured_separator() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    create_dir_all(dir.path().join("Nomino")).unwrap();
+    let _ = File::create(dir.path().join("Nomino").join("S1.E1.mkv")).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--sanitize",
+            "posix",
+            "--depth",
+            "2",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r"(.*)\.mkv",
+            "{1}/{1}",
+        ])
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stderr.as_slice()).contains(
+        "'Nomino/S1.E1/Nomino/S1.E1.mkv' <- 'Nomino/S1.E1.mkv': has more directories than its input"
+    ));
+    assert!(dir.path().join("Nomino").join("S1.E1.mkv").is_file());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--sanitize",
+            "posix",
+            "--depth",
+            "2",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r"(.*)\.mkv",
+            "{1}.E2",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("Nomino").join("S1.E1.E2.mkv").is_file());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_default_truncate() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let input = "Nomino (2020) S1.E1.1080p.mkv";
+    let _ = File::create(dir.path().join(input)).unwrap();
+    let output = "a".repeat(300);
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["-d", dir.path().to_str().unwrap(), ".*", output.as_str()])
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stderr.as_slice()).contains("(304 bytes)"));
+    assert!(dir.path().join(input).is_file());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--truncate=ellipsis",
+            "-d",
+            dir.path().to_str().unwrap(),
+            ".*",
+            output.as_str(),
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(
+        dir.path()
+            .join(format!("{}….mkv", "a".repeat(248)))
+            .is_file()
+    );
+
+    dir.close().unwrap();
+}
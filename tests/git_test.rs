@@ -0,0 +1,204 @@
+use assert_cmd::Command;
+use std::fs::{File, create_dir, read_to_string, write};
+use std::path::Path;
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args([
+            "-c",
+            "user.name=nomino",
+            "-c",
+            "user.email=nomino@localhost",
+        ])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+fn init(dir: &Path) {
+    git(dir, &["init", "-q"]);
+    write(dir.join("tracked.txt"), "tracked").unwrap();
+    write(dir.join("other.txt"), "other").unwrap();
+    git(dir, &["add", "."]);
+    git(dir, &["commit", "-q", "-m", "init"]);
+}
+
+#[test]
+fn test_git() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    init(dir.path());
+    let _ = File::create(dir.path().join("untracked.txt")).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--git",
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            "(tracked|untracked).txt",
+            "{1}-renamed.txt",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    let status = git(dir.path(), &["status", "--porcelain"]);
+    let mut lines: Vec<&str> = status.lines().collect();
+    lines.sort();
+    assert_eq!(
+        lines,
+        [
+            "?? untracked-renamed.txt",
+            "R  tracked.txt -> tracked-renamed.txt"
+        ]
+    );
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_git_nested() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    create_dir(dir.path().join("d1")).unwrap();
+    write(dir.path().join("d1").join("a"), "a").unwrap();
+    init(dir.path());
+    let map = state.path().join("map.json");
+    write(map.as_path(), r#"{"d1/a": "d1/b", "d1": "d2"}"#).unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--git",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-m",
+            map.to_str().unwrap(),
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("d2").join("b").is_file());
+    assert_eq!(
+        git(dir.path(), &["status", "--porcelain"]).trim_end(),
+        "R  d1/a -> d2/b"
+    );
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_git_skips_git_dir() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    init(dir.path());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "--max-depth",
+            "3",
+            "-r",
+            "(.*)",
+            "{1}.bak",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join(".git/HEAD").exists());
+    assert!(dir.path().join("tracked.txt.bak").exists());
+    assert!(dir.path().join("other.txt.bak").exists());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_git_update_refs_skips_git_dir() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    init(dir.path());
+    write(dir.path().join("README.md"), "see tracked.txt").unwrap();
+    write(dir.path().join(".git/COMMIT_EDITMSG"), "add tracked.txt").unwrap();
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "--update-refs",
+            "**",
+            "-d",
+            dir.path().to_str().unwrap(),
+            r"tracked\.txt",
+            "renamed.txt",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert_eq!(
+        read_to_string(dir.path().join("README.md")).unwrap(),
+        "see renamed.txt"
+    );
+    assert_eq!(
+        read_to_string(dir.path().join(".git/COMMIT_EDITMSG")).unwrap(),
+        "add tracked.txt"
+    );
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_git_dirty_output() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    init(dir.path());
+    write(dir.path().join("other.txt"), "modified").unwrap();
+
+    let args = [
+        "--git",
+        "-E",
+        "-w",
+        "-d",
+        dir.path().to_str().unwrap(),
+        "-r",
+        "tracked.txt",
+        "other.txt",
+    ];
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert!(dir.path().join("tracked.txt").exists());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .arg("--force")
+        .args(args)
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(!dir.path().join("tracked.txt").exists());
+    let status = git(dir.path(), &["status", "--porcelain"]);
+    let mut lines: Vec<&str> = status.lines().collect();
+    lines.sort();
+    assert_eq!(lines, ["D  tracked.txt", "M  other.txt"]);
+
+    dir.close().unwrap();
+}
@@ -0,0 +1,267 @@
+mod common;
+
+use assert_cmd::Command;
+use common::list_files;
+use std::fs::File;
+use std::io::Write;
+
+fn nomino(dir: &tempfile::TempDir, state: &tempfile::TempDir, args: &[&str]) -> Command {
+    let mut cmd = Command::cargo_bin(env!("CARGO_PKG_NAME")).unwrap();
+    cmd.env("NOMINO_STATE_DIR", state.path())
+        .args(args)
+        .args(["-d", dir.path().to_str().unwrap()]);
+    cmd
+}
+
+#[test]
+fn test_history_undo_redo() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+
+    let inputs = vec![
+        "Nomino (2020) S1.E1.1080p.mkv",
+        "Nomino (2020) S1.E2.1080p.mkv",
+        "Nomino (2020) S1.E3.1080p.mkv",
+    ];
+    let outputs = vec!["01.mkv", "02.mkv", "03.mkv"];
+
+    for input in inputs.iter() {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = nomino(&dir, &state, &["-E", r".*E(\d+).*", "{:2}.mkv"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), outputs);
+
+    let cmd = nomino(&dir, &state, &["undo"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), inputs);
+
+    let cmd = nomino(&dir, &state, &["undo"]).output().unwrap();
+    assert!(!cmd.status.success());
+
+    let cmd = nomino(&dir, &state, &["redo"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), outputs);
+
+    let cmd = nomino(&dir, &state, &["history"]).unwrap();
+    assert!(cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stdout.as_slice()).contains("done"));
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
+
+#[test]
+fn test_history_commands_with_options() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    let _ = File::create(dir.path().join("a.txt")).unwrap();
+
+    let cmd = nomino(&dir, &state, &["-E", "a.txt", "b.txt"]).unwrap();
+    assert!(cmd.status.success());
+
+    let cmd = nomino(&dir, &state, &["-t", "-s", "asc", "undo"]).unwrap();
+    assert!(cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stdout.as_slice()).contains("undo.txt"));
+    assert_eq!(list_files(&dir), ["b.txt"]);
+
+    let cmd = nomino(&dir, &state, &["-t", "-r", "b", "recover"]).unwrap();
+    assert!(cmd.status.success());
+    assert!(String::from_utf8_lossy(cmd.stdout.as_slice()).contains("recover.txt"));
+
+    let cmd = nomino(&dir, &state, &["undo", "-t"]).output().unwrap();
+    assert!(!cmd.status.success());
+    assert_eq!(list_files(&dir), ["b.txt"]);
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
+
+#[test]
+fn test_history_undo_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("dir1").join("sub1")).unwrap();
+    let _ = File::create(dir.path().join("dir1").join("sub1").join("file1")).unwrap();
+    let args = [
+        "-E",
+        "--depth",
+        "1",
+        "--max-depth",
+        "3",
+        "-r",
+        "(.*)1",
+        "{1}2",
+    ];
+
+    let cmd = nomino(&dir, &state, &args).unwrap();
+    assert!(cmd.status.success());
+    assert!(dir.path().join("dir2").join("sub2").join("file2").is_file());
+
+    let cmd = nomino(&dir, &state, &["undo"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), ["dir1"]);
+    assert!(dir.path().join("dir1").join("sub1").join("file1").is_file());
+
+    let cmd = nomino(&dir, &state, &["redo"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), ["dir2"]);
+    assert!(dir.path().join("dir2").join("sub2").join("file2").is_file());
+
+    let _ = File::create(dir.path().join("dir2").join("sub2").join("file2"))
+        .unwrap()
+        .write_all(b"modified");
+    let cmd = nomino(&dir, &state, &["undo"]).output().unwrap();
+    assert!(!cmd.status.success());
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
+
+#[test]
+fn test_history_undo_modified() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+
+    let _ = File::create(dir.path().join("a.txt")).unwrap();
+
+    let cmd = nomino(&dir, &state, &["-r", "a.txt", "b.txt", "-E"]).unwrap();
+    assert!(cmd.status.success());
+
+    let mut file = File::options()
+        .append(true)
+        .open(dir.path().join("b.txt"))
+        .unwrap();
+    file.write_all(b"modified").unwrap();
+    file.sync_all().unwrap();
+
+    let cmd = nomino(&dir, &state, &["undo"]).output().unwrap();
+    assert!(!cmd.status.success());
+    assert_eq!(list_files(&dir), ["b.txt"]);
+
+    let cmd = nomino(&dir, &state, &["undo", "--force"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), ["a.txt"]);
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
+
+#[test]
+fn test_history_update_refs() {
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+
+    std::fs::create_dir_all(dir.path().join("img")).unwrap();
+    std::fs::create_dir_all(dir.path().join("docs")).unwrap();
+    let _ = File::create(dir.path().join("img").join("a.png")).unwrap();
+    let readme = "![a](img/a.png) ![other](img/a.png.bak)\n";
+    let index = "![a](../img/a.png)\n";
+    std::fs::write(dir.path().join("README.md"), readme).unwrap();
+    std::fs::write(dir.path().join("docs").join("index.md"), index).unwrap();
+
+    let cmd = nomino(
+        &dir,
+        &state,
+        &["-E", "--update-refs", "*.md", r"img/a\.png", "img/b.png"],
+    )
+    .unwrap();
+    assert!(cmd.status.success());
+    assert!(dir.path().join("img").join("b.png").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("README.md")).unwrap(),
+        "![a](img/b.png) ![other](img/a.png.bak)\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("docs").join("index.md")).unwrap(),
+        "![a](../img/b.png)\n"
+    );
+
+    let cmd = nomino(&dir, &state, &["undo"]).unwrap();
+    assert!(cmd.status.success());
+    assert!(dir.path().join("img").join("a.png").exists());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("README.md")).unwrap(),
+        readme
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("docs").join("index.md")).unwrap(),
+        index
+    );
+
+    let cmd = nomino(&dir, &state, &["redo"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("docs").join("index.md")).unwrap(),
+        "![a](../img/b.png)\n"
+    );
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_history_retarget_symlinks() {
+    use std::os::unix::fs::symlink;
+    use std::path::Path;
+
+    let dir = tempfile::tempdir().unwrap();
+    let state = tempfile::tempdir().unwrap();
+    let root = dir.path().canonicalize().unwrap();
+
+    std::fs::create_dir_all(root.join("data")).unwrap();
+    std::fs::create_dir_all(root.join("links")).unwrap();
+    let _ = File::create(root.join("data").join("a.txt")).unwrap();
+    symlink("../data/a.txt", root.join("links").join("rel")).unwrap();
+    symlink(
+        root.join("data").join("a.txt"),
+        root.join("links").join("abs"),
+    )
+    .unwrap();
+
+    let cmd = nomino(
+        &dir,
+        &state,
+        &["-E", "--retarget-symlinks", r"^data/a\.txt$", "data/b.txt"],
+    )
+    .unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(
+        std::fs::read_link(root.join("links").join("rel")).unwrap(),
+        Path::new("../data/b.txt")
+    );
+    assert_eq!(
+        std::fs::read_link(root.join("links").join("abs")).unwrap(),
+        root.join("data").join("b.txt")
+    );
+
+    // The symlink is renamed itself, without following it.
+    let cmd = nomino(
+        &dir,
+        &state,
+        &["-E", "--retarget-symlinks", "^links/rel$", "rel"],
+    )
+    .unwrap();
+    assert!(cmd.status.success());
+    assert!(root.join("data").join("b.txt").is_file());
+    assert_eq!(
+        std::fs::read_link(root.join("rel")).unwrap(),
+        Path::new("data/b.txt")
+    );
+
+    let cmd = nomino(&dir, &state, &["undo", "2"]).unwrap();
+    assert!(cmd.status.success());
+    assert_eq!(
+        std::fs::read_link(root.join("links").join("rel")).unwrap(),
+        Path::new("../data/a.txt")
+    );
+    assert_eq!(
+        std::fs::read_link(root.join("links").join("abs")).unwrap(),
+        root.join("data").join("a.txt")
+    );
+
+    dir.close().unwrap();
+    state.close().unwrap();
+}
@@ -1,10 +1,14 @@
+#![allow(clippy::useless_vec, clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use std::fs::File;
 use std::fs::read_dir;
+use std::fs::read_to_string;
 use std::io::Write;
 
 #[test]
 fn test_map() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -49,6 +53,7 @@ fn test_map() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -74,6 +79,7 @@ fn test_map() {
 
     let cmd_undo = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&["-E", "-d", dir.path().to_str().unwrap(), "-m", "undo.json"])
         .unwrap();
 
@@ -96,3 +102,198 @@ fn test_map() {
 
     dir.close().unwrap();
 }
+
+#[test]
+fn test_map_cycle() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut map = File::create(dir.path().join("map.json")).unwrap();
+    map.write_all(
+        r#"{
+        "a.txt": "b.txt",
+        "b.txt": "c.txt",
+        "c.txt": "a.txt",
+        "1.txt": "2.txt",
+        "2.txt": "3.txt",
+        "3.txt": "4.txt"
+    }"#
+        .as_bytes(),
+    )
+    .unwrap();
+    map.sync_all().unwrap();
+
+    for input in ["a.txt", "b.txt", "c.txt", "1.txt", "2.txt", "3.txt"] {
+        let mut file = File::create(dir.path().join(input)).unwrap();
+        file.write_all(input.as_bytes()).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["-d", dir.path().to_str().unwrap(), "-m", "map.json"])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+
+    assert!(cmd.status.success());
+    assert_eq!(
+        files,
+        [
+            "2.txt", "3.txt", "4.txt", "a.txt", "b.txt", "c.txt", "map.json"
+        ]
+    );
+    for (output, input) in [
+        ("a.txt", "c.txt"),
+        ("b.txt", "a.txt"),
+        ("c.txt", "b.txt"),
+        ("2.txt", "1.txt"),
+        ("3.txt", "2.txt"),
+        ("4.txt", "3.txt"),
+    ] {
+        assert_eq!(read_to_string(dir.path().join(output)).unwrap(), input);
+    }
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_map_atomic() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut map = File::create(dir.path().join("map.json")).unwrap();
+    map.write_all(
+        r#"{
+        "a.txt": "new/a.txt",
+        "b.txt": "c.txt",
+        "missing.txt": "d.txt"
+    }"#
+        .as_bytes(),
+    )
+    .unwrap();
+    map.sync_all().unwrap();
+
+    for input in ["a.txt", "b.txt"] {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-k",
+            "--atomic",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-m",
+            "map.json",
+        ])
+        .output()
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+
+    assert!(!cmd.status.success());
+    assert_eq!(files, ["a.txt", "b.txt", "map.json"]);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_map_atomic_overwrite() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut map = File::create(dir.path().join("map.json")).unwrap();
+    map.write_all(r#"{"a.txt": "b.txt"}"#.as_bytes()).unwrap();
+    map.sync_all().unwrap();
+
+    for input in ["a.txt", "b.txt"] {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    for args in [
+        ["-w", "--atomic"],
+        ["--on-conflict=keep-larger", "--atomic"],
+    ] {
+        let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+            .unwrap()
+            .env("NOMINO_STATE_DIR", state.path())
+            .args(args)
+            .args(["-d", dir.path().to_str().unwrap(), "-m", "map.json"])
+            .output()
+            .unwrap();
+
+        assert!(!cmd.status.success());
+        assert!(String::from_utf8_lossy(cmd.stderr.as_slice()).contains("'--atomic'"));
+    }
+
+    assert!(dir.path().join("a.txt").is_file());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_map_outside() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let work = dir.path().join("work");
+    std::fs::create_dir_all(work.join("dir")).unwrap();
+
+    let mut map = File::create(work.join("map.json")).unwrap();
+    map.write_all(
+        r#"{
+        "a.txt": "../a.txt",
+        "b.txt": "dir/../c.txt"
+    }"#
+        .as_bytes(),
+    )
+    .unwrap();
+    map.sync_all().unwrap();
+
+    for input in ["a.txt", "b.txt"] {
+        let _ = File::create(work.join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["-d", work.to_str().unwrap(), "-m", "map.json"])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(cmd.stderr.as_slice()).to_string();
+    assert!(!cmd.status.success());
+    assert!(stderr.contains("'../a.txt'"));
+    assert!(!stderr.contains("'dir/../c.txt'"));
+    assert!(work.join("a.txt").is_file());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "--allow-outside",
+            "-d",
+            work.to_str().unwrap(),
+            "-m",
+            "map.json",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("a.txt").is_file());
+    assert!(work.join("c.txt").is_file());
+
+    dir.close().unwrap();
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use assert_cmd::Command;
+use common::list_files;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+
+const JOURNAL: &str = r#"{"start":{"on_conflict":"prefix","mkdir":true,"mode":"move"}}
+{"plan":{"rename":{"input":"a.txt","source":"a.txt","output":"new/b.txt"}}}
+{"plan":{"rename":{"input":"c.txt","source":"c.txt","output":"d.txt"}}}
+{"mkdir":{"path":"new"}}
+{"begin":{"source":"a.txt","target":"new/b.txt","mode":"move"}}
+{"end"#;
+
+/// Path of the journal of `dir` in `state`, keyed by the FNV-1a hash of its
+/// canonical path.
+fn journal_path(dir: &tempfile::TempDir, state: &tempfile::TempDir) -> PathBuf {
+    let directory = std::fs::canonicalize(dir.path()).unwrap();
+    let hash = directory
+        .as_os_str()
+        .as_encoded_bytes()
+        .iter()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+    state
+        .path()
+        .join("nomino")
+        .join("journal")
+        .join(format!("{hash:016x}.journal"))
+}
+
+fn interrupted_batch(state: &tempfile::TempDir) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let path = journal_path(&dir, state);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let mut journal = File::create(path).unwrap();
+    journal.write_all(JOURNAL.as_bytes()).unwrap();
+    journal.sync_all().unwrap();
+    std::fs::create_dir(dir.path().join("new")).unwrap();
+    let _ = File::create(dir.path().join("new").join("b.txt")).unwrap();
+    let _ = File::create(dir.path().join("c.txt")).unwrap();
+    dir
+}
+
+#[test]
+fn test_recover_complete() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = interrupted_batch(&state);
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["recover", "-d", dir.path().to_str().unwrap()])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), ["d.txt", "new"]);
+    assert!(!journal_path(&dir, &state).exists());
+    assert!(dir.path().join("new").join("b.txt").exists());
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_recover_revert() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = interrupted_batch(&state);
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["recover", "--revert", "-d", dir.path().to_str().unwrap()])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert_eq!(list_files(&dir), ["a.txt", "c.txt"]);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_recover_stale_journal() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = interrupted_batch(&state);
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(["-d", dir.path().to_str().unwrap(), "-r", "c.txt", "e.txt"])
+        .output()
+        .unwrap();
+
+    assert!(!cmd.status.success());
+    assert_eq!(list_files(&dir), ["c.txt", "new"]);
+    assert!(journal_path(&dir, &state).exists());
+
+    dir.close().unwrap();
+}
@@ -1,11 +1,16 @@
+#![allow(clippy::useless_vec, clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use nomino::input::MAIN_SEPARATOR;
 use std::fs::File;
 use std::fs::create_dir_all;
 use std::fs::read_dir;
+use std::fs::read_to_string;
+use std::io::Write;
 
 #[test]
 fn test_regex() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -24,6 +29,7 @@ fn test_regex() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -51,6 +57,7 @@ fn test_regex() {
 
 #[test]
 fn test_named_regex() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -69,6 +76,7 @@ fn test_named_regex() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -96,6 +104,7 @@ fn test_named_regex() {
 
 #[test]
 fn test_regex_not_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -114,8 +123,10 @@ fn test_regex_not_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-r",
@@ -141,6 +152,7 @@ fn test_regex_not_overwrite() {
 
 #[test]
 fn test_regex_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -159,8 +171,10 @@ fn test_regex_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-w",
@@ -187,6 +201,7 @@ fn test_regex_overwrite() {
 
 #[test]
 fn test_regex_subdir() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -210,6 +225,7 @@ fn test_regex_subdir() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",
@@ -247,6 +263,7 @@ fn test_regex_subdir() {
 
 #[test]
 fn test_regex_subdir_depth() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -270,6 +287,7 @@ fn test_regex_subdir_depth() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "--depth",
@@ -309,6 +327,7 @@ fn test_regex_subdir_depth() {
 
 #[test]
 fn test_regex_subdir_max_depth() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -332,6 +351,7 @@ fn test_regex_subdir_max_depth() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "--depth",
@@ -373,6 +393,7 @@ fn test_regex_subdir_max_depth() {
 
 #[test]
 fn test_regex_subdir_not_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -396,8 +417,10 @@ fn test_regex_subdir_not_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-k",
@@ -433,6 +456,7 @@ fn test_regex_subdir_not_overwrite() {
 
 #[test]
 fn test_regex_subdir_overwrite() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     create_dir_all(dir.path().join("s1")).unwrap();
@@ -456,8 +480,10 @@ fn test_regex_subdir_overwrite() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
+            "--allow-conflicts",
             "-d",
             dir.path().to_str().unwrap(),
             "-k",
@@ -491,3 +517,233 @@ fn test_regex_subdir_overwrite() {
 
     dir.close().unwrap();
 }
+
+#[test]
+fn test_regex_directories() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    create_dir_all(dir.path().join("dir1").join("sub1")).unwrap();
+    let _ = File::create(dir.path().join("dir1").join("sub1").join("file1")).unwrap();
+
+    let map = state.path().join("map.json");
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "-g",
+            map.to_str().unwrap(),
+            "--depth",
+            "1",
+            "--max-depth",
+            "3",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            "(.*)1",
+            "{1}2",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    assert!(dir.path().join("dir2").join("sub2").join("file2").is_file());
+    assert_eq!(read_dir(dir.path()).unwrap().count(), 1);
+    // The map refers to the final paths, so that it can be used to undo the run.
+    let map: serde_json::Value =
+        serde_json::from_str(read_to_string(map).unwrap().as_str()).unwrap();
+    let path = |names: &[&str]| names.join(std::path::MAIN_SEPARATOR_STR);
+    assert_eq!(
+        map,
+        serde_json::json!({
+            "dir2": "dir1",
+            path(&["dir2", "sub2"]): path(&["dir1", "sub1"]),
+            path(&["dir2", "sub2", "file2"]): path(&["dir1", "sub1", "file1"]),
+        })
+    );
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_regex_filters() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = vec![
+        "the.big.bang (2007) S1.E1.1080p.mkv",
+        "the.big.bang (2007) S1.E2.1080p.mkv",
+    ];
+
+    let mut outputs = ["THE_BIG_BANG-e1.mkv", "THE_BIG_BANG-e2.mkv"];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            r"(?<title>.*) \(\d+\) S\d+\.(E\d+).*",
+            "{title|snake|upper}-{2|lower}.mkv",
+        ])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+    outputs.sort();
+
+    assert!(cmd.status.success());
+    assert_eq!(files, outputs);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_regex_optional_groups() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = vec!["show.S01E02.mkv", "show.S01E03.finale.mkv", "show.mkv"];
+
+    let mut outputs = [
+        "show - e02.mkv",
+        "show - e03 - finale.mkv",
+        "show - e00.mkv",
+    ];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            r"(\w+)(?:\.S\d+E(\d+))?(?:\.(\w+))?\.mkv",
+            "{1} - e{2?0:2}{?3: - {3}}.mkv",
+        ])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+    outputs.sort();
+
+    assert!(cmd.status.success());
+    assert_eq!(files, outputs);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_regex_arithmetic() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    let inputs = vec!["page0.jpg", "page1.jpg", "page10.jpg", "cover.jpg"];
+
+    for input in inputs {
+        let _ = File::create(dir.path().join(input)).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            r"(\w+)\.jpg",
+            "{1+1:3}.jpg",
+        ])
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(cmd.stderr.as_slice()).to_string();
+    assert!(!cmd.status.success());
+    assert!(stderr.contains("requires an integer but got '"));
+    assert!(dir.path().join("page0.jpg").is_file());
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args(&[
+            "-E",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            r"page(\d+)\.jpg",
+            "{1+1:3}.jpg",
+        ])
+        .unwrap();
+
+    let mut files: Vec<String> = read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_str().unwrap().to_string())
+        .collect();
+
+    files.sort();
+
+    assert!(cmd.status.success());
+    assert_eq!(files, ["001.jpg", "002.jpg", "011.jpg", "cover.jpg"]);
+
+    dir.close().unwrap();
+}
+
+#[test]
+fn test_regex_name_order() {
+    let state = tempfile::tempdir().unwrap();
+    let dir = tempfile::tempdir().unwrap();
+
+    // Created in reverse order, so that the order of the directory differs
+    // from the order of the names on most filesystems.
+    for input in ["e.txt", "d.txt", "c.txt", "b.txt", "a.txt"] {
+        let mut file = File::create(dir.path().join(input)).unwrap();
+        file.write_all(input.as_bytes()).unwrap();
+    }
+
+    let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
+        .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
+        .args([
+            "-E",
+            "--allow-conflicts",
+            "-d",
+            dir.path().to_str().unwrap(),
+            "-r",
+            r".\.txt",
+            "out.txt",
+        ])
+        .unwrap();
+
+    assert!(cmd.status.success());
+    for (output, input) in [
+        ("out.txt", "a.txt"),
+        ("_out.txt", "b.txt"),
+        ("__out.txt", "c.txt"),
+        ("___out.txt", "d.txt"),
+        ("____out.txt", "e.txt"),
+    ] {
+        assert_eq!(read_to_string(dir.path().join(output)).unwrap(), input);
+    }
+
+    dir.close().unwrap();
+}
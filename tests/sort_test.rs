@@ -1,9 +1,12 @@
+#![allow(clippy::useless_vec, clippy::needless_borrows_for_generic_args)]
+
 use assert_cmd::Command;
 use std::fs::File;
 use std::fs::read_dir;
 
 #[test]
 fn test_sort() {
+    let state = tempfile::tempdir().unwrap();
     let dir = tempfile::tempdir().unwrap();
 
     let inputs = vec![
@@ -22,6 +25,7 @@ fn test_sort() {
 
     let cmd = Command::cargo_bin(env!("CARGO_PKG_NAME"))
         .unwrap()
+        .env("NOMINO_STATE_DIR", state.path())
         .args(&[
             "-E",
             "-d",